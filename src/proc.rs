@@ -23,7 +23,14 @@ pub struct Environment {
     pub kit_imports: BTreeMap<String, PathBuf>,
     /// Maps asset input paths to their final output paths from the
     /// previous convergence pass. Used to resolve [ContextValue::AssetRef].
+    ///
+    /// Pages after the first of a paginated asset are keyed
+    /// as `{path}#page/{number}`.
     pub asset_outputs: BTreeMap<String, String>,
+
+    /// Templates parsed during the build, shared across convergence
+    /// passes so each template and part is only parsed once.
+    pub templates: Arc<template::TemplateCache>,
 }
 
 #[cfg(test)]
@@ -33,6 +40,7 @@ impl Environment {
             source_root: std::path::PathBuf::from("."),
             kit_imports: Default::default(),
             asset_outputs: BTreeMap::new(),
+            templates: Default::default(),
         }
     }
 }
@@ -125,6 +133,17 @@ impl LayeredContext {
             .extend(entries);
     }
 
+    /// Inserts entries only if the key is not present in the topmost overlay.
+    pub fn fill_top(&mut self, entries: Context) {
+        let top = self
+            .overlays
+            .last_mut()
+            .expect("fill_top requires at least one overlay");
+        for (key, value) in entries {
+            top.entry(key).or_insert(value);
+        }
+    }
+
     /// Inserts entries only if the key is not present in any layer.
     pub fn fill(&mut self, entries: Context) {
         for (key, value) in entries {
@@ -199,6 +218,14 @@ impl LayeredContext {
 #[derive(Debug, Clone)]
 pub enum ContextValue {
     Text(Text),
+    /// A whole number, like `weight = 10`.
+    Integer(i64),
+    /// A number with a fractional part, like `price = 9.99`.
+    Number(f64),
+    /// A boolean, like `draft = true`.
+    Bool(bool),
+    /// A date with an optional time, like `date = 2025-04-17`.
+    Date(toml::value::Datetime),
     List(Vec<ContextValue>),
     Table(Context),
     /// A reference to another asset by its input path.
@@ -259,18 +286,37 @@ impl ContextValue {
     pub fn from_toml(value: toml::Value) -> Result<Self, ProcessingError> {
         match value {
             toml::Value::String(s) => Ok(ContextValue::Text(s.into())),
-            toml::Value::Integer(n) => Ok(ContextValue::Text(n.to_string().into())),
-            toml::Value::Float(n) => Ok(ContextValue::Text(n.to_string().into())),
-            toml::Value::Boolean(b) => Ok(ContextValue::Text(b.to_string().into())),
+            toml::Value::Integer(n) => Ok(ContextValue::Integer(n)),
+            toml::Value::Float(n) => Ok(ContextValue::Number(n)),
+            toml::Value::Boolean(b) => Ok(ContextValue::Bool(b)),
             toml::Value::Array(arr) => {
                 let items: Result<Vec<ContextValue>, _> =
                     arr.into_iter().map(ContextValue::from_toml).collect();
                 Ok(ContextValue::List(items?))
             }
             toml::Value::Table(table) => Ok(ContextValue::Table(context_from_toml(table)?)),
+            // Times without a date (like `07:30:00`) remain text.
+            toml::Value::Datetime(dt) if dt.date.is_some() => Ok(ContextValue::Date(dt)),
             toml::Value::Datetime(dt) => Ok(ContextValue::Text(dt.to_string().into())),
         }
     }
+
+    /// Returns the text of a text, number, boolean, or date value, or
+    /// `None` if this value is a list, table, or asset reference.
+    ///
+    /// Integers are written exactly, numbers without a fractional part
+    /// if they're whole (`10`, not `10.0`), and dates exactly as they
+    /// were in TOML.
+    pub fn to_text(&self) -> Option<Text> {
+        match self {
+            ContextValue::Text(text) => Some(text.clone()),
+            ContextValue::Integer(integer) => Some(integer.to_string().into()),
+            ContextValue::Number(number) => Some(number.to_string().into()),
+            ContextValue::Bool(bool) => Some(bool.to_string().into()),
+            ContextValue::Date(date) => Some(date.to_string().into()),
+            ContextValue::List(_) | ContextValue::Table(_) | ContextValue::AssetRef(_) => None,
+        }
+    }
 }
 
 /// An error that occurs while procesing assets.
@@ -288,4 +334,22 @@ pub enum ProcessingError {
     /// An error occurred while compiling an asset
     /// via a processor.
     Compilation { message: Text },
+
+    /// An error occurred while compiling a template,
+    /// at a known location in the template's source.
+    Template {
+        diagnostic: Box<template::TemplateDiagnostic>,
+    },
+}
+
+impl std::fmt::Display for ProcessingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessingError::NonTextual => write!(f, "expected textual content"),
+            ProcessingError::NonBinary => write!(f, "expected binary content"),
+            ProcessingError::Malformed { message } => write!(f, "malformed content: {}", message),
+            ProcessingError::Compilation { message } => write!(f, "{}", message),
+            ProcessingError::Template { diagnostic } => write!(f, "{}", diagnostic),
+        }
+    }
 }
@@ -3,7 +3,7 @@ use codas::types::Text;
 mod media_type;
 pub use media_type::{MediaCategory, MediaType};
 
-use crate::proc::ProcessingError;
+use crate::proc::{Context, ContextValue, ProcessingError};
 
 /// An in-memory representation of any asset meant for processing.
 #[derive(Clone, Debug)]
@@ -16,6 +16,9 @@ pub struct Asset {
     path: Text,
     content: Option<AssetContent>,
     content_media_type: MediaType,
+    /// Metadata produced by processors, which is
+    /// merged into the asset's context after processing.
+    metadata: Context,
 }
 
 impl Asset {
@@ -38,6 +41,7 @@ impl Asset {
             path,
             content_media_type: media_type,
             content: contents,
+            metadata: Context::new(),
         }
     }
 
@@ -103,6 +107,16 @@ impl Asset {
             _ => Err(ProcessingError::NonBinary),
         }
     }
+
+    /// Inserts `value` at `key` in the asset's metadata.
+    pub fn insert_metadata(&mut self, key: Text, value: ContextValue) {
+        self.metadata.insert(key, value);
+    }
+
+    /// Removes and returns all of the asset's metadata.
+    pub fn take_metadata(&mut self) -> Context {
+        std::mem::take(&mut self.metadata)
+    }
 }
 
 /// Raw content of an [Asset].
@@ -1,9 +1,37 @@
 use std::collections::HashMap;
+use std::ops::{Range, RangeInclusive};
 
-use markdown::mdast::{AlignKind, Node};
+use markdown::mdast::{AlignKind, Node, ReferenceKind};
 use markdown::message::Message;
 
-use super::{Asset, Environment, LayeredContext, MediaType, ProcessesAssets, ProcessingError};
+use super::{
+    Asset, Context, ContextValue, Environment, LayeredContext, MediaType, ProcessesAssets,
+    ProcessingError,
+};
+
+mod highlight;
+mod typography;
+
+pub use highlight::stylesheet as highlight_stylesheet;
+pub use typography::{Locale, Punctuation};
+
+/// Key in an asset's metadata containing the outline of its headings.
+pub const TOC_CONTEXT_KEY: &str = "toc";
+
+/// Keys in an asset's metadata containing its excerpt as plain text and HTML.
+pub const EXCERPT_CONTEXT_KEY: &str = "excerpt";
+pub const EXCERPT_HTML_CONTEXT_KEY: &str = "excerpt_html";
+
+/// Keys in an asset's metadata containing its number
+/// of words and estimated reading time (in minutes).
+pub const WORD_COUNT_CONTEXT_KEY: &str = "word_count";
+pub const READING_TIME_CONTEXT_KEY: &str = "reading_time";
+
+/// Marker ending a document's excerpt.
+const EXCERPT_MARKER: &str = "<!-- more -->";
+
+/// Words read per minute, for estimating reading time.
+const WORDS_PER_MINUTE: usize = 200;
 
 impl From<Message> for ProcessingError {
     fn from(error: Message) -> Self {
@@ -12,7 +40,41 @@ impl From<Message> for ProcessingError {
         }
     }
 }
-pub struct MarkdownProcessor {}
+
+/// Compiles Markdown assets into HTML.
+pub struct MarkdownProcessor {
+    /// Whether to highlight the syntax of fenced code blocks.
+    highlight: bool,
+    /// Whether to number the lines of highlighted code blocks.
+    line_numbers: bool,
+    /// Depths of the headings included in the table of contents.
+    toc_depth: RangeInclusive<u8>,
+    /// Punctuation applied to text.
+    punctuation: Punctuation,
+}
+
+impl MarkdownProcessor {
+    /// Creates a new markdown processor.
+    pub fn new(
+        highlight: bool,
+        line_numbers: bool,
+        toc_depth: RangeInclusive<u8>,
+        punctuation: Punctuation,
+    ) -> Self {
+        Self {
+            highlight,
+            line_numbers,
+            toc_depth,
+            punctuation,
+        }
+    }
+}
+
+impl Default for MarkdownProcessor {
+    fn default() -> Self {
+        Self::new(false, false, 1..=6, Punctuation::default())
+    }
+}
 
 impl ProcessesAssets for MarkdownProcessor {
     fn process(
@@ -46,8 +108,53 @@ impl ProcessesAssets for MarkdownProcessor {
 
         // Compile the AST into HTML.
         let mut compiled_html = String::with_capacity(text.len());
-        let mut state = CompileState::default();
-        compile_ast_node(None, &ast, &mut compiled_html, &mut state);
+        let mut state = CompileState {
+            highlight: self.highlight,
+            line_numbers: self.line_numbers,
+            punctuation: self.punctuation,
+            ..CompileState::default()
+        };
+        state.definitions.collect(&ast);
+        compile_ast_node(&ast, &mut compiled_html, &mut state);
+
+        // Collect the outline of headings within the configured depths.
+        let headings: Vec<_> = state
+            .headings
+            .into_iter()
+            .filter(|heading| self.toc_depth.contains(&heading.depth))
+            .collect();
+        asset.insert_metadata(
+            TOC_CONTEXT_KEY.into(),
+            ContextValue::List(outline(&headings)),
+        );
+
+        // Collect the excerpt and reading statistics.
+        let children = ast.children().map(Vec::as_slice).unwrap_or_default();
+        let excerpt = excerpt_range(children);
+        let excerpt_text = children[excerpt.clone()]
+            .iter()
+            .map(Node::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let excerpt_html =
+            &compiled_html[state.root_offsets[excerpt.start]..state.root_offsets[excerpt.end]];
+        asset.insert_metadata(
+            EXCERPT_CONTEXT_KEY.into(),
+            ContextValue::Text(excerpt_text.into()),
+        );
+        asset.insert_metadata(
+            EXCERPT_HTML_CONTEXT_KEY.into(),
+            ContextValue::Text(excerpt_html.into()),
+        );
+        let words = count_words(&ast);
+        asset.insert_metadata(
+            WORD_COUNT_CONTEXT_KEY.into(),
+            ContextValue::Integer(words as i64),
+        );
+        asset.insert_metadata(
+            READING_TIME_CONTEXT_KEY.into(),
+            ContextValue::Integer(words.div_ceil(WORDS_PER_MINUTE) as i64),
+        );
 
         // Update the asset's contents and target extension.
         asset.replace_with_text(compiled_html.into(), MediaType::Html);
@@ -57,12 +164,7 @@ impl ProcessesAssets for MarkdownProcessor {
 
 /// Compiles a Markdown AST `node` associated
 /// with an `asset` into `compiled_html`.
-fn compile_ast_node(
-    parent_node: Option<&Node>,
-    node: &Node,
-    compiled_html: &mut String,
-    state: &mut CompileState,
-) {
+fn compile_ast_node(node: &Node, compiled_html: &mut String, state: &mut CompileState) {
     match node {
         // Document root node.
         Node::Root(_) => {
@@ -132,6 +234,11 @@ fn compile_ast_node(
             *compiled_html += " id=\"";
             *compiled_html += &unique_id;
             *compiled_html += "\">";
+            state.headings.push(Heading {
+                depth: heading.depth,
+                text: heading_str,
+                id: unique_id,
+            });
 
             // Compile the actual header contents.
             compile_ast_node_children(node, compiled_html, state);
@@ -157,34 +264,63 @@ fn compile_ast_node(
 
         // Inline link.
         Node::Link(link) => {
-            let link_url = &link.url;
-
-            // Emit HTML.
-            *compiled_html += "<a href=\"";
-            *compiled_html += &link_url.replace('\"', "").replace("\\\"", "");
-            if let Some(title) = link.title.as_ref() {
-                *compiled_html += "\" title=\"";
-                *compiled_html += &title.replace('\"', "&quot;").replace("\\\"", "&quot;");
-            }
-            *compiled_html += "\">";
+            emit_link_open(&link.url, link.title.as_deref(), compiled_html);
             compile_ast_node_children(node, compiled_html, state);
             *compiled_html += "</a>";
         }
 
         // Inline image.
         Node::Image(image) => {
-            let image_url = &image.url;
-
-            // Emit HTML.
-            *compiled_html += "<img alt=\"";
-            *compiled_html += &image.alt.replace('\"', "&quot;").replace("\\\"", "&quot;");
-            *compiled_html += "\" src=\"";
-            *compiled_html += image_url;
-            if let Some(title) = image.title.as_ref() {
-                *compiled_html += "\" title=\"";
-                *compiled_html += &title.replace('\"', "&quot;").replace("\\\"", "&quot;");
+            emit_image(
+                &image.alt,
+                &image.url,
+                image.title.as_deref(),
+                compiled_html,
+            );
+        }
+
+        // Reference link (`[text][ref]`, `[ref][]`, or `[ref]`).
+        Node::LinkReference(reference) => {
+            match state.definitions.get(&reference.identifier) {
+                Some((url, title)) => {
+                    emit_link_open(&url, title.as_deref(), compiled_html);
+                    compile_ast_node_children(node, compiled_html, state);
+                    *compiled_html += "</a>";
+                }
+
+                // Unresolved references are literal text.
+                None => {
+                    *compiled_html += "[";
+                    compile_ast_node_children(node, compiled_html, state);
+                    *compiled_html += "]";
+                    emit_reference_label(
+                        &reference.reference_kind,
+                        reference.label.as_deref(),
+                        compiled_html,
+                    );
+                }
+            }
+        }
+
+        // Reference image (`![alt][ref]`, `![ref][]`, or `![ref]`).
+        Node::ImageReference(reference) => {
+            match state.definitions.get(&reference.identifier) {
+                Some((url, title)) => {
+                    emit_image(&reference.alt, &url, title.as_deref(), compiled_html);
+                }
+
+                // Unresolved references are literal text.
+                None => {
+                    *compiled_html += "![";
+                    *compiled_html += &reference.alt;
+                    *compiled_html += "]";
+                    emit_reference_label(
+                        &reference.reference_kind,
+                        reference.label.as_deref(),
+                        compiled_html,
+                    );
+                }
             }
-            *compiled_html += "\">";
         }
 
         // Break (line break).
@@ -204,38 +340,61 @@ fn compile_ast_node(
 
         // Raw text.
         Node::Text(text) => {
-            // FIXME: Extended markdown behavior.
-            // If this text is a direct descendant of a
-            // block-level text node, convert `--` to
-            // em dashes (`—`).
-            if matches!(parent_node, Some(Node::Paragraph(..))) {
-                *compiled_html += &text.value.replace("--", "—");
-            } else {
-                *compiled_html += &text.value;
+            match state.punctuation {
+                Punctuation::Smart(locale) => escape(
+                    &typography::smarten(&text.value, state.previous_char, locale),
+                    compiled_html,
+                ),
+                Punctuation::Dashes if state.in_paragraph => {
+                    escape(&text.value.replace("--", "—"), compiled_html);
+                }
+                _ => escape(&text.value, compiled_html),
             }
+            state.previous_char = text.value.chars().next_back().or(state.previous_char);
         }
 
         // Inline code.
         Node::InlineCode(code) => {
+            state.previous_char = code.value.chars().next_back().or(state.previous_char);
             *compiled_html += "<code>";
-            *compiled_html += &code.value;
+            escape(&code.value, compiled_html);
             *compiled_html += "</code>";
         }
 
         // Fenced code block.
         Node::Code(code) => {
             // FIXME: Extended markdown behavior.
+            *compiled_html += "<pre";
             if let Some(lang) = &code.lang {
-                *compiled_html += "<pre rel=\"";
+                *compiled_html += " rel=\"";
                 *compiled_html += lang;
-                *compiled_html += "\"><code class=\"language-";
+                *compiled_html += "\"";
+            }
+            if state.highlight {
+                *compiled_html += " class=\"highlight";
+                if state.line_numbers {
+                    *compiled_html += " line-numbers";
+                }
+                *compiled_html += "\"";
+            }
+            *compiled_html += "><code";
+            if let Some(lang) = &code.lang {
+                *compiled_html += " class=\"language-";
                 *compiled_html += lang;
-                *compiled_html += "\">";
-            } else {
-                *compiled_html += "<pre><code>";
+                *compiled_html += "\"";
             }
+            *compiled_html += ">";
 
-            *compiled_html += &code.value;
+            if state.highlight {
+                *compiled_html += &highlight::highlight(
+                    code.lang.as_deref(),
+                    code.meta.as_deref(),
+                    &code.value,
+                    state.line_numbers,
+                );
+            } else {
+                escape(&code.value, compiled_html);
+            }
             *compiled_html += "</code></pre>";
         }
 
@@ -246,10 +405,8 @@ fn compile_ast_node(
             *compiled_html += "</s>";
         }
 
-        // Definitions are not yet supported.
-        Node::Definition(_) => {
-            tracing::warn!("unsupported markdown node: definition");
-        }
+        // Definitions are resolved by references, and not rendered.
+        Node::Definition(_) => {}
 
         // Footnote definition: rendered inline by compile_ast_node_children.
         // This branch handles the content inside the <section> wrapper.
@@ -263,7 +420,7 @@ fn compile_ast_node(
             // Compile footnote content.
             let mut inner = String::new();
             for child in node.children().unwrap() {
-                compile_ast_node(Some(node), child, &mut inner, state);
+                compile_ast_node(child, &mut inner, state);
             }
 
             // Insert the back-link inside the last <p> tag.
@@ -296,11 +453,6 @@ fn compile_ast_node(
             *compiled_html += "]</a></sup>";
         }
 
-        // Link/image references are not yet supported.
-        Node::LinkReference(_) | Node::ImageReference(_) => {
-            tracing::warn!("unsupported markdown node: link/image reference");
-        }
-
         // GFM table.
         Node::Table(table) => {
             *compiled_html += "<table>";
@@ -370,7 +522,19 @@ fn compile_ast_node_children(node: &Node, compiled_html: &mut String, state: &mu
     let children = node.children().unwrap();
     let mut in_footnote_section = false;
 
+    // Text in each block starts a new run of smart punctuation.
+    if !is_inline(node) {
+        state.previous_char = None;
+    }
+
     for child in children {
+        state.in_paragraph = matches!(node, Node::Paragraph(_));
+
+        // Record where each of the document's top-level nodes starts.
+        if matches!(node, Node::Root(_)) {
+            state.root_offsets.push(compiled_html.len());
+        }
+
         let is_footnote = matches!(child, Node::FootnoteDefinition(_));
 
         if is_footnote && !in_footnote_section {
@@ -388,7 +552,12 @@ fn compile_ast_node_children(node: &Node, compiled_html: &mut String, state: &mu
             in_footnote_section = false;
         }
 
-        compile_ast_node(Some(node), child, compiled_html, state);
+        compile_ast_node(child, compiled_html, state);
+    }
+
+    // Record where each of the document's top-level nodes ends.
+    if matches!(node, Node::Root(_)) {
+        state.root_offsets.push(compiled_html.len());
     }
 
     if in_footnote_section {
@@ -396,6 +565,45 @@ fn compile_ast_node_children(node: &Node, compiled_html: &mut String, state: &mu
     }
 }
 
+/// Emits an opening `<a>` tag linking to `url`, with an optional `title`.
+fn emit_link_open(url: &str, title: Option<&str>, compiled_html: &mut String) {
+    *compiled_html += "<a href=\"";
+    *compiled_html += &url.replace('\"', "").replace("\\\"", "");
+    if let Some(title) = title {
+        *compiled_html += "\" title=\"";
+        *compiled_html += &title.replace('\"', "&quot;").replace("\\\"", "&quot;");
+    }
+    *compiled_html += "\">";
+}
+
+/// Emits an `<img>` tag of the image at `url`, with an optional `title`.
+fn emit_image(alt: &str, url: &str, title: Option<&str>, compiled_html: &mut String) {
+    *compiled_html += "<img alt=\"";
+    *compiled_html += &alt.replace('\"', "&quot;").replace("\\\"", "&quot;");
+    *compiled_html += "\" src=\"";
+    *compiled_html += url;
+    if let Some(title) = title {
+        *compiled_html += "\" title=\"";
+        *compiled_html += &title.replace('\"', "&quot;").replace("\\\"", "&quot;");
+    }
+    *compiled_html += "\">";
+}
+
+/// Emits the label following the text of an unresolved reference,
+/// as it was written: `[label]` for full references, `[]` for
+/// collapsed references, and nothing for shortcut references.
+fn emit_reference_label(kind: &ReferenceKind, label: Option<&str>, compiled_html: &mut String) {
+    match kind {
+        ReferenceKind::Full => {
+            *compiled_html += "[";
+            *compiled_html += label.unwrap_or_default();
+            *compiled_html += "]";
+        }
+        ReferenceKind::Collapsed => *compiled_html += "[]",
+        ReferenceKind::Shortcut => {}
+    }
+}
+
 /// Emits an opening `<th>` or `<td>` tag with an optional `align` attribute.
 fn emit_cell_tag(tag: &str, align: Option<&AlignKind>, compiled_html: &mut String) {
     *compiled_html += "<";
@@ -412,8 +620,163 @@ fn emit_cell_tag(tag: &str, align: Option<&AlignKind>, compiled_html: &mut Strin
 /// Mutable state threaded through AST compilation.
 #[derive(Default)]
 struct CompileState {
+    highlight: bool,
+    line_numbers: bool,
+    punctuation: Punctuation,
+    /// True if the node being compiled is directly inside a paragraph.
+    in_paragraph: bool,
+    /// The last character of text compiled in the
+    /// current block, for smart punctuation.
+    previous_char: Option<char>,
+    /// Offsets in the compiled HTML where each of the document's
+    /// top-level nodes starts, followed by the end of the last node.
+    root_offsets: Vec<usize>,
     footnotes: Footnotes,
     heading_ids: HeadingIds,
+    headings: Vec<Heading>,
+    definitions: Definitions,
+}
+
+/// A heading compiled into a document.
+struct Heading {
+    depth: u8,
+    text: String,
+    id: String,
+}
+
+/// Returns the outline of `headings` as a list of `{level, text, id, children}`
+/// tables, with each heading's `children` containing the deeper headings
+/// following it (up until the next heading at the same or a shallower depth).
+fn outline(headings: &[Heading]) -> Vec<ContextValue> {
+    let mut items = vec![];
+    let mut i = 0;
+    while i < headings.len() {
+        let heading = &headings[i];
+        let end = headings[i + 1..]
+            .iter()
+            .position(|next| next.depth <= heading.depth)
+            .map_or(headings.len(), |n| i + 1 + n);
+
+        let mut item = Context::new();
+        item.insert("level".into(), ContextValue::Integer(heading.depth.into()));
+        item.insert(
+            "text".into(),
+            ContextValue::Text(heading.text.as_str().into()),
+        );
+        item.insert("id".into(), ContextValue::Text(heading.id.as_str().into()));
+        item.insert(
+            "children".into(),
+            ContextValue::List(outline(&headings[i + 1..end])),
+        );
+        items.push(ContextValue::Table(item));
+        i = end;
+    }
+    items
+}
+
+/// Returns the range of a document's top-level `children` in its excerpt:
+/// the children before an [`EXCERPT_MARKER`], or its first paragraph.
+fn excerpt_range(children: &[Node]) -> Range<usize> {
+    let marker = children
+        .iter()
+        .position(|child| matches!(child, Node::Html(html) if html.value.trim() == EXCERPT_MARKER));
+    if let Some(marker) = marker {
+        return 0..marker;
+    }
+
+    children
+        .iter()
+        .position(|child| matches!(child, Node::Paragraph(_)))
+        .map_or(0..0, |paragraph| paragraph..paragraph + 1)
+}
+
+/// Returns the number of words in the text and code of `node`.
+fn count_words(node: &Node) -> usize {
+    /// Appends the text of `node` to `text`, separating blocks with spaces.
+    fn collect_text(node: &Node, text: &mut String) {
+        match node {
+            Node::Text(node) => *text += &node.value,
+            Node::InlineCode(code) => *text += &code.value,
+            Node::Code(code) => *text += &code.value,
+            node => {
+                for child in node.children().into_iter().flatten() {
+                    collect_text(child, text);
+                }
+            }
+        }
+        if !is_inline(node) {
+            text.push(' ');
+        }
+    }
+
+    let mut text = String::new();
+    collect_text(node, &mut text);
+    text.split_whitespace().count()
+}
+
+/// Appends `text` to `html`, escaping HTML special characters.
+fn escape(text: &str, html: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => *html += "&amp;",
+            '<' => *html += "&lt;",
+            '>' => *html += "&gt;",
+            c => html.push(c),
+        }
+    }
+}
+
+/// Returns true if `node` is inline content, like text, emphasis, or a link.
+fn is_inline(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Text(_)
+            | Node::InlineCode(_)
+            | Node::Emphasis(_)
+            | Node::Strong(_)
+            | Node::Delete(_)
+            | Node::Link(_)
+            | Node::LinkReference(_)
+            | Node::Html(_)
+    )
+}
+
+/// Link and image reference definitions (`[label]: url "title"`).
+#[derive(Default)]
+struct Definitions {
+    /// Maps each normalized label to its URL and optional title.
+    definitions: HashMap<String, (String, Option<String>)>,
+}
+
+impl Definitions {
+    /// Collects all definitions in `node` and its descendants.
+    /// If a label is defined more than once, the first definition wins.
+    fn collect(&mut self, node: &Node) {
+        if let Node::Definition(definition) = node {
+            self.definitions
+                .entry(Self::normalize(&definition.identifier))
+                .or_insert_with(|| (definition.url.clone(), definition.title.clone()));
+        }
+
+        for child in node.children().into_iter().flatten() {
+            self.collect(child);
+        }
+    }
+
+    /// Returns the URL and title defined for the reference `identifier`.
+    fn get(&self, identifier: &str) -> Option<(String, Option<String>)> {
+        self.definitions.get(&Self::normalize(identifier)).cloned()
+    }
+
+    /// Normalizes a label for case-insensitive matching,
+    /// collapsing whitespace and folding letters to lowercase.
+    fn normalize(label: &str) -> String {
+        label
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    }
 }
 
 /// Tracks footnote numbering during compilation.
@@ -466,8 +829,12 @@ mod tests {
     use crate::proc::LayeredContext;
 
     fn compile(md: &str) -> String {
+        compile_with(MarkdownProcessor::default(), md)
+    }
+
+    fn compile_with(processor: MarkdownProcessor, md: &str) -> String {
         let mut asset = Asset::new("test.md".into(), md.as_bytes().to_vec());
-        MarkdownProcessor {}
+        processor
             .process(
                 &Environment::test(),
                 &LayeredContext::from_flat(Default::default()),
@@ -495,6 +862,56 @@ mod tests {
         assert!(html.contains("id=\"foo-3\""));
     }
 
+    #[test]
+    fn table_of_contents() {
+        let md = "# Title\n## Setup\n### Install\n## Usage\n#### Flags\n## Usage";
+        let mut asset = Asset::new("test.md".into(), md.as_bytes().to_vec());
+        MarkdownProcessor::new(false, false, 2..=4, Punctuation::Plain)
+            .process(
+                &Environment::test(),
+                &LayeredContext::from_flat(Default::default()),
+                &mut asset,
+            )
+            .unwrap();
+
+        let mut metadata = asset.take_metadata();
+        let Some(ContextValue::List(toc)) = metadata.remove(&TOC_CONTEXT_KEY.into()) else {
+            panic!("expected a toc list");
+        };
+        let headings: Vec<_> = toc
+            .iter()
+            .map(|item| {
+                let ContextValue::Table(item) = item else {
+                    panic!("expected a toc table");
+                };
+                let children = match item.get(&"children".into()) {
+                    Some(ContextValue::List(children)) => children.len(),
+                    _ => panic!("expected toc children"),
+                };
+                (
+                    item.get(&"level".into())
+                        .and_then(ContextValue::to_text)
+                        .unwrap(),
+                    item.get(&"text".into())
+                        .and_then(ContextValue::to_text)
+                        .unwrap(),
+                    item.get(&"id".into())
+                        .and_then(ContextValue::to_text)
+                        .unwrap(),
+                    children,
+                )
+            })
+            .collect();
+        assert_eq!(
+            headings,
+            [
+                ("2".into(), "Setup".into(), "setup".into(), 1),
+                ("2".into(), "Usage".into(), "usage".into(), 1),
+                ("2".into(), "Usage".into(), "usage-2".into(), 0),
+            ]
+        );
+    }
+
     #[test]
     fn inline_links() {
         let html = compile("[Click here](https://example.com)");
@@ -525,6 +942,21 @@ mod tests {
         assert!(html.contains(r#"title="A photo""#));
     }
 
+    #[test]
+    fn reference_links_and_images() {
+        let html = compile(
+            "[Full][Docs], [docs][], [Docs], and ![Logo][LOGO].\n\n[docs]: https://example.com \"The Docs\"\n[logo]: /logo.png",
+        );
+        assert_eq!(
+            html,
+            "<p><a href=\"https://example.com\" title=\"The Docs\">Full</a>, <a href=\"https://example.com\" title=\"The Docs\">docs</a>, <a href=\"https://example.com\" title=\"The Docs\">Docs</a>, and <img alt=\"Logo\" src=\"/logo.png\">.</p>"
+        );
+
+        // Unresolved references are literal text.
+        let html = compile("[text][missing] and [missing]");
+        assert_eq!(html, "<p>[text][missing] and [missing]</p>");
+    }
+
     #[test]
     fn inline_code() {
         let html = compile("Use `println!` to print.");
@@ -545,6 +977,29 @@ mod tests {
         assert!(html.contains("<pre><code>plain code</code></pre>"));
     }
 
+    #[test]
+    fn highlighted_code_block() {
+        let md = "```rust {2}\nlet a = 1;\nlet b = a;\n```";
+        let html = compile_with(
+            MarkdownProcessor::new(true, false, 1..=6, Punctuation::Plain),
+            md,
+        );
+        assert!(html.contains(
+            r#"<pre rel="rust" class="highlight"><code class="language-rust"><span class="line">"#
+        ));
+        assert!(html.contains(
+            r#"<span class="line highlighted"><span class="hl-keyword">let</span> b = a;</span>"#
+        ));
+
+        let html = compile_with(
+            MarkdownProcessor::new(true, true, 1..=6, Punctuation::Plain),
+            "```\na < b\n```",
+        );
+        assert!(html.contains(
+            r#"<pre class="highlight line-numbers"><code><span class="line">a &lt; b</span></code></pre>"#
+        ));
+    }
+
     #[test]
     fn unordered_list() {
         let html = compile("- one\n- two\n- three");
@@ -597,12 +1052,96 @@ mod tests {
         assert!(html.contains("Hello \u{2014} world"));
     }
 
+    #[test]
+    fn excerpts_and_reading_time() {
+        let metadata = |md: &str| {
+            let mut asset = Asset::new("test.md".into(), md.as_bytes().to_vec());
+            MarkdownProcessor::new(false, false, 1..=6, Punctuation::Plain)
+                .process(
+                    &Environment::test(),
+                    &LayeredContext::from_flat(Default::default()),
+                    &mut asset,
+                )
+                .unwrap();
+            let metadata = asset.take_metadata();
+            let get = |key: &str| metadata.get(&key.into()).and_then(ContextValue::to_text);
+            [
+                get(EXCERPT_CONTEXT_KEY),
+                get(EXCERPT_HTML_CONTEXT_KEY),
+                get(WORD_COUNT_CONTEXT_KEY),
+                get(READING_TIME_CONTEXT_KEY),
+            ]
+            .map(|value| value.unwrap().to_string())
+        };
+
+        // The excerpt defaults to the first paragraph.
+        assert_eq!(
+            metadata("# Title\n\nFirst *one*.\n\nSecond `two` three."),
+            ["First one.", "<p>First <em>one</em>.</p>", "6", "1"]
+        );
+
+        // The excerpt ends at a marker.
+        assert_eq!(
+            metadata("One.\n\n- Two\n\n<!-- more -->\n\nThree."),
+            [
+                "One. Two",
+                "<p>One.</p><ul><li><p>Two</p></li></ul>",
+                "3",
+                "1"
+            ]
+        );
+
+        assert_eq!(metadata(&"word ".repeat(401))[3], "3");
+
+        // Documents without words take no time to read.
+        assert_eq!(metadata("<!-- more -->")[2..], ["0", "0"]);
+    }
+
+    #[test]
+    fn smart_punctuation() {
+        let md = "\"*Quoted*\" -- it's `--flag \"x\"`...\n\n```\na -- \"b\"\n```";
+        let processor =
+            MarkdownProcessor::new(false, false, 1..=6, Punctuation::Smart(Locale::English));
+        let html = compile_with(processor, md);
+        assert!(html.contains("<p>“<em>Quoted</em>” — it’s <code>--flag \"x\"</code>…</p>"));
+        assert!(html.contains("<pre><code>a -- \"b\"</code></pre>"));
+
+        // By default, only dashes directly inside paragraphs are converted.
+        let html = compile(md);
+        assert!(html.contains("<p>\"<em>Quoted</em>\" — it's <code>--flag \"x\"</code>...</p>"));
+        let html = compile("Use *a -- b* -- c");
+        assert!(html.contains("<p>Use <em>a -- b</em> — c</p>"));
+
+        let processor = MarkdownProcessor::new(false, false, 1..=6, Punctuation::Plain);
+        let html = compile_with(processor, "Use --flag \"x\"...");
+        assert!(html.contains("<p>Use --flag \"x\"...</p>"));
+
+        let processor =
+            MarkdownProcessor::new(false, false, 1..=6, Punctuation::Smart(Locale::French));
+        let html = compile_with(processor, "Il a dit \"oui\" !");
+        assert!(html.contains("<p>Il a dit «\u{a0}oui\u{a0}»\u{202f}!</p>"));
+    }
+
     #[test]
     fn thematic_break() {
         let html = compile("above\n\n---\n\nbelow");
         assert!(html.contains("<hr/>"));
     }
 
+    #[test]
+    fn escapes_text_and_code() {
+        let html = compile("# &lt;script&gt; a < b & c\n\n`<b>` and\n\n```\n<i>&amp;</i>\n```");
+        assert!(
+            html.contains("&lt;script&gt; a &lt; b &amp; c</h1>"),
+            "{html}"
+        );
+        assert!(html.contains("<code>&lt;b&gt;</code>"), "{html}");
+        assert!(
+            html.contains("<code>&lt;i&gt;&amp;amp;&lt;/i&gt;</code>"),
+            "{html}"
+        );
+    }
+
     #[test]
     fn raw_html_passthrough() {
         let html = compile("<div class=\"custom\">content</div>");
@@ -633,7 +1172,7 @@ mod tests {
     #[test]
     fn skips_non_markdown() {
         let mut asset = Asset::new("page.html".into(), b"<h1>Hello</h1>".to_vec());
-        let modified = MarkdownProcessor {}
+        let modified = MarkdownProcessor::default()
             .process(
                 &Environment::test(),
                 &LayeredContext::from_flat(Default::default()),
@@ -0,0 +1,1097 @@
+//! Build-time syntax highlighting for fenced code blocks.
+//!
+//! Code is split into tokens by a small hand-written scanner for
+//! each supported language, and each token is wrapped in a
+//! `<span class="hl-{class}">`, styled by the [`stylesheet`].
+
+use std::ops::{Range, RangeInclusive};
+
+use super::escape;
+
+/// Classes of highlighted tokens.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Class {
+    Comment,
+    String,
+    Number,
+    Keyword,
+    Type,
+    Function,
+    Property,
+    Tag,
+    Variable,
+}
+
+impl Class {
+    /// All classes, in the order they're styled.
+    const ALL: [Class; 9] = [
+        Class::Comment,
+        Class::String,
+        Class::Number,
+        Class::Keyword,
+        Class::Type,
+        Class::Function,
+        Class::Property,
+        Class::Tag,
+        Class::Variable,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Class::Comment => "comment",
+            Class::String => "string",
+            Class::Number => "number",
+            Class::Keyword => "keyword",
+            Class::Type => "type",
+            Class::Function => "function",
+            Class::Property => "property",
+            Class::Tag => "tag",
+            Class::Variable => "variable",
+        }
+    }
+
+    /// Returns the default color of this class in the [`stylesheet`].
+    fn color(self) -> &'static str {
+        match self {
+            Class::Comment => "#6a737d",
+            Class::String => "#032f62",
+            Class::Number => "#005cc5",
+            Class::Keyword => "#d73a49",
+            Class::Type => "#6f42c1",
+            Class::Function => "#6f42c1",
+            Class::Property => "#005cc5",
+            Class::Tag => "#22863a",
+            Class::Variable => "#e36209",
+        }
+    }
+}
+
+/// A span of source code and its class, if any.
+type Token = (Option<Class>, Range<usize>);
+
+/// Languages supported by the highlighter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Language {
+    Rust,
+    JavaScript,
+    Html,
+    Css,
+    Scss,
+    Toml,
+    Shell,
+    Glsl,
+}
+
+impl Language {
+    /// Returns the language named by a code block's info string.
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "rust" | "rs" => Some(Language::Rust),
+            "js" | "javascript" | "jsx" | "mjs" | "cjs" | "ts" | "typescript" | "tsx" => {
+                Some(Language::JavaScript)
+            }
+            "html" | "htm" | "xml" | "svg" => Some(Language::Html),
+            "css" => Some(Language::Css),
+            "scss" => Some(Language::Scss),
+            "toml" => Some(Language::Toml),
+            "sh" | "bash" | "shell" | "zsh" => Some(Language::Shell),
+            "glsl" | "vert" | "frag" => Some(Language::Glsl),
+            _ => None,
+        }
+    }
+}
+
+/// Lexical rules of a C-like language.
+struct Syntax {
+    line_comment: Option<&'static str>,
+    block_comment: Option<(&'static str, &'static str)>,
+    /// Quotes of strings which may contain `\` escapes.
+    quotes: &'static [char],
+    /// Quotes of strings which may not contain escapes.
+    raw_quotes: &'static [char],
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    /// Whether capitalized identifiers are types.
+    capitalized_types: bool,
+    /// Whether identifiers followed by `!` are macros.
+    macros: bool,
+    /// Whether `'` starts lifetimes and character literals, and
+    /// `b` and `r` prefix byte and raw strings (as in Rust).
+    rust_literals: bool,
+    /// Whether lines starting with `#` are preprocessor directives.
+    preprocessor: bool,
+    /// Prefix of variable names (like `$` in shell scripts).
+    sigil: Option<char>,
+}
+
+const RUST: Syntax = Syntax {
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    raw_quotes: &[],
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "unsafe", "use", "where", "while", "yield",
+    ],
+    types: &[
+        "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+        "i64", "i128", "isize", "f32", "f64",
+    ],
+    capitalized_types: true,
+    macros: true,
+    rust_literals: true,
+    preprocessor: false,
+    sigil: None,
+};
+
+const JAVASCRIPT: Syntax = Syntax {
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"', '\'', '`'],
+    raw_quotes: &[],
+    keywords: &[
+        "abstract",
+        "as",
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "debugger",
+        "declare",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "enum",
+        "export",
+        "extends",
+        "false",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "implements",
+        "import",
+        "in",
+        "instanceof",
+        "interface",
+        "keyof",
+        "let",
+        "namespace",
+        "new",
+        "null",
+        "of",
+        "private",
+        "protected",
+        "public",
+        "readonly",
+        "return",
+        "static",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "type",
+        "typeof",
+        "undefined",
+        "var",
+        "void",
+        "while",
+        "with",
+        "yield",
+    ],
+    types: &[
+        "any", "bigint", "boolean", "never", "number", "object", "string", "symbol", "unknown",
+    ],
+    capitalized_types: true,
+    macros: false,
+    rust_literals: false,
+    preprocessor: false,
+    sigil: None,
+};
+
+const GLSL: Syntax = Syntax {
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    raw_quotes: &[],
+    keywords: &[
+        "attribute",
+        "break",
+        "buffer",
+        "case",
+        "centroid",
+        "const",
+        "continue",
+        "default",
+        "discard",
+        "do",
+        "else",
+        "false",
+        "flat",
+        "for",
+        "highp",
+        "if",
+        "in",
+        "inout",
+        "invariant",
+        "layout",
+        "lowp",
+        "mediump",
+        "out",
+        "precision",
+        "return",
+        "shared",
+        "smooth",
+        "struct",
+        "switch",
+        "true",
+        "uniform",
+        "varying",
+        "while",
+    ],
+    types: &[
+        "void",
+        "bool",
+        "int",
+        "uint",
+        "float",
+        "double",
+        "vec2",
+        "vec3",
+        "vec4",
+        "bvec2",
+        "bvec3",
+        "bvec4",
+        "ivec2",
+        "ivec3",
+        "ivec4",
+        "uvec2",
+        "uvec3",
+        "uvec4",
+        "dvec2",
+        "dvec3",
+        "dvec4",
+        "mat2",
+        "mat3",
+        "mat4",
+        "mat2x2",
+        "mat2x3",
+        "mat2x4",
+        "mat3x2",
+        "mat3x3",
+        "mat3x4",
+        "mat4x2",
+        "mat4x3",
+        "mat4x4",
+        "sampler2D",
+        "sampler3D",
+        "samplerCube",
+        "sampler2DShadow",
+        "sampler2DArray",
+    ],
+    capitalized_types: false,
+    macros: false,
+    rust_literals: false,
+    preprocessor: true,
+    sigil: None,
+};
+
+const SHELL: Syntax = Syntax {
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"'],
+    raw_quotes: &['\''],
+    keywords: &[
+        "alias", "break", "case", "continue", "declare", "do", "done", "elif", "else", "esac",
+        "exit", "export", "fi", "for", "function", "if", "in", "local", "readonly", "return",
+        "select", "shift", "source", "then", "time", "unset", "until", "while",
+    ],
+    types: &[],
+    capitalized_types: false,
+    macros: false,
+    rust_literals: false,
+    preprocessor: false,
+    sigil: Some('$'),
+};
+
+/// Highlights `code` written in `language` as HTML.
+///
+/// Code in unsupported languages is escaped without highlighting.
+/// If `line_numbers` is set or `meta` specifies highlighted line
+/// ranges (like `{1,3-5}`), each line is wrapped in a
+/// `<span class="line">`, with highlighted lines marked
+/// as `<span class="line highlighted">`.
+pub(super) fn highlight(
+    language: Option<&str>,
+    meta: Option<&str>,
+    code: &str,
+    line_numbers: bool,
+) -> String {
+    let tokens = match language.and_then(Language::from_name) {
+        Some(language) => tokenize(language, code),
+        None => vec![(None, 0..code.len())],
+    };
+    let lines = render_lines(code, &tokens);
+
+    let ranges = meta.map(line_ranges).unwrap_or_default();
+    if !line_numbers && ranges.is_empty() {
+        return lines.join("\n");
+    }
+
+    let mut html = String::with_capacity(code.len() * 2);
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            html.push('\n');
+        }
+        if ranges.iter().any(|range| range.contains(&(i + 1))) {
+            html += "<span class=\"line highlighted\">";
+        } else {
+            html += "<span class=\"line\">";
+        }
+        html += line;
+        html += "</span>";
+    }
+    html
+}
+
+/// Parses the line ranges in braces of a code block's
+/// `meta` string, like `{3-5}` or `{1,4-6}`.
+fn line_ranges(meta: &str) -> Vec<RangeInclusive<usize>> {
+    let Some(ranges) = meta
+        .split_once('{')
+        .and_then(|(_, ranges)| Some(ranges.split_once('}')?.0))
+    else {
+        return vec![];
+    };
+
+    ranges
+        .split(',')
+        .filter_map(|range| {
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            Some(start.trim().parse().ok()?..=end.trim().parse().ok()?)
+        })
+        .collect()
+}
+
+/// Renders each line of `code` as escaped HTML, wrapping classified
+/// `tokens` in spans. Tokens spanning several lines are split, so
+/// that each line's spans are balanced.
+fn render_lines(code: &str, tokens: &[Token]) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for (class, range) in tokens {
+        for (i, text) in code[range.clone()].split('\n').enumerate() {
+            if i > 0 {
+                lines.push(String::new());
+            }
+            if text.is_empty() {
+                continue;
+            }
+
+            let line = lines.last_mut().expect("lines are never empty");
+            match class {
+                Some(class) => {
+                    *line += "<span class=\"hl-";
+                    *line += class.name();
+                    *line += "\">";
+                    escape(text, line);
+                    *line += "</span>";
+                }
+                None => escape(text, line),
+            }
+        }
+    }
+    lines
+}
+
+/// Splits `code` written in `language` into tokens.
+fn tokenize(language: Language, code: &str) -> Vec<Token> {
+    let mut scanner = Scanner::new(code);
+    match language {
+        Language::Rust => scanner.scan_code(&RUST),
+        Language::JavaScript => scanner.scan_code(&JAVASCRIPT),
+        Language::Glsl => scanner.scan_code(&GLSL),
+        Language::Shell => scanner.scan_code(&SHELL),
+        Language::Html => scanner.scan_html(),
+        Language::Css => scanner.scan_css(false),
+        Language::Scss => scanner.scan_css(true),
+        Language::Toml => scanner.scan_toml(),
+    }
+    scanner.tokens
+}
+
+/// Scans source code into a list of tokens.
+struct Scanner<'a> {
+    source: &'a str,
+    position: usize,
+    tokens: Vec<Token>,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            position: 0,
+            tokens: vec![],
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    /// Returns the next character that isn't a space or tab.
+    fn peek_past_spaces(&self) -> Option<char> {
+        self.rest().chars().find(|c| *c != ' ' && *c != '\t')
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn bump_while(&mut self, f: impl Fn(char) -> bool) {
+        while self.peek().is_some_and(&f) {
+            self.bump();
+        }
+    }
+
+    /// Consumes `prefix` if the rest of the source starts with it.
+    fn eat(&mut self, prefix: &str) -> bool {
+        let eaten = self.rest().starts_with(prefix);
+        if eaten {
+            self.position += prefix.len();
+        }
+        eaten
+    }
+
+    /// Consumes everything up to and including `end`,
+    /// or the rest of the source if there's no `end`.
+    fn bump_past(&mut self, end: &str) {
+        match self.rest().find(end) {
+            Some(i) => self.position += i + end.len(),
+            None => self.position = self.source.len(),
+        }
+    }
+
+    /// Consumes the rest of a string after its opening `quote`.
+    fn bump_string(&mut self, quote: char, escapes: bool) {
+        while let Some(c) = self.bump() {
+            if c == quote {
+                break;
+            } else if c == '\\' && escapes {
+                self.bump();
+            }
+        }
+    }
+
+    /// Adds a token from `start` to the current position, merging
+    /// it into the previous token if they have the same class.
+    fn emit(&mut self, start: usize, class: Option<Class>) {
+        if start == self.position {
+            return;
+        }
+        match self.tokens.last_mut() {
+            Some((last, range)) if *last == class && range.end == start => {
+                range.end = self.position;
+            }
+            _ => self.tokens.push((class, start..self.position)),
+        }
+    }
+
+    /// Scans the source as a C-like language described by `syntax`.
+    fn scan_code(&mut self, syntax: &Syntax) {
+        let mut line_start = true;
+        while let Some(c) = self.peek() {
+            let start = self.position;
+            let class = if syntax.line_comment.is_some_and(|prefix| self.eat(prefix))
+                || (syntax.preprocessor && line_start && c == '#')
+            {
+                self.bump_while(|c| c != '\n');
+                match c {
+                    '#' if syntax.preprocessor => Some(Class::Keyword),
+                    _ => Some(Class::Comment),
+                }
+            } else if let Some((open, close)) = syntax.block_comment
+                && self.eat(open)
+            {
+                self.bump_past(close);
+                Some(Class::Comment)
+            } else if syntax.quotes.contains(&c) || syntax.raw_quotes.contains(&c) {
+                self.bump();
+                self.bump_string(c, !syntax.raw_quotes.contains(&c));
+                Some(Class::String)
+            } else if syntax.rust_literals && c == '\'' {
+                self.scan_rust_quote()
+            } else if syntax.rust_literals && self.scan_rust_string() {
+                Some(Class::String)
+            } else if c.is_ascii_digit() {
+                self.scan_number();
+                Some(Class::Number)
+            } else if syntax.sigil == Some(c) {
+                self.bump();
+                if self.eat("{") {
+                    self.bump_past("}");
+                } else {
+                    self.bump_while(|c| c.is_alphanumeric() || "_?@#*!".contains(c));
+                }
+                Some(Class::Variable)
+            } else if c.is_alphabetic() || c == '_' {
+                self.bump_while(|c| c.is_alphanumeric() || c == '_');
+                self.classify_word(syntax, &self.source[start..self.position])
+            } else {
+                self.bump();
+                None
+            };
+            self.emit(start, class);
+
+            if c == '\n' {
+                line_start = true;
+            } else if !c.is_whitespace() {
+                line_start = false;
+            }
+        }
+    }
+
+    /// Classifies an identifier `word` that was just consumed.
+    fn classify_word(&mut self, syntax: &Syntax, word: &str) -> Option<Class> {
+        if syntax.keywords.contains(&word) {
+            Some(Class::Keyword)
+        } else if syntax.types.contains(&word)
+            || (syntax.capitalized_types && word.starts_with(|c: char| c.is_uppercase()))
+        {
+            Some(Class::Type)
+        } else if syntax.macros && self.peek() == Some('!') && self.peek_nth(1) != Some('=') {
+            self.bump();
+            Some(Class::Function)
+        } else if self.peek() == Some('(') {
+            Some(Class::Function)
+        } else {
+            None
+        }
+    }
+
+    /// Consumes a number, including any suffix or unit.
+    fn scan_number(&mut self) {
+        while let Some(c) = self.peek() {
+            let is_fraction = c == '.' && self.peek_nth(1).is_some_and(|c| c.is_ascii_digit());
+            if c.is_alphanumeric() || c == '_' || is_fraction {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Consumes a Rust character literal or lifetime.
+    fn scan_rust_quote(&mut self) -> Option<Class> {
+        self.bump();
+        let is_char = self.peek() == Some('\\') || self.peek_nth(1) == Some('\'');
+        if is_char {
+            self.bump_string('\'', true);
+            Some(Class::String)
+        } else {
+            self.bump_while(|c| c.is_alphanumeric() || c == '_');
+            Some(Class::Variable)
+        }
+    }
+
+    /// Consumes a Rust byte or raw string, like `b"..."`
+    /// or `r#"..."#`, returning true if one was found.
+    fn scan_rust_string(&mut self) -> bool {
+        let rest = self.rest();
+        let prefix = rest.strip_prefix('b').unwrap_or(rest);
+        let start = self.position + rest.len() - prefix.len();
+
+        if let Some(raw) = prefix.strip_prefix('r') {
+            let hashes = raw.len() - raw.trim_start_matches('#').len();
+            if raw[hashes..].starts_with('"') {
+                self.position = start + 1 + hashes + 1;
+                self.bump_past(&format!("\"{}", "#".repeat(hashes)));
+                return true;
+            }
+        } else if prefix.len() < rest.len() && (prefix.starts_with('"') || prefix.starts_with('\''))
+        {
+            self.position = start;
+            let quote = self.bump().expect("quote was just found");
+            self.bump_string(quote, true);
+            return true;
+        }
+        false
+    }
+
+    /// Scans the source as CSS, or SCSS if `scss` is set.
+    fn scan_css(&mut self, scss: bool) {
+        let mut depth = 0usize;
+        while let Some(c) = self.peek() {
+            let start = self.position;
+            let is_word = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+            let class = if self.eat("/*") {
+                self.bump_past("*/");
+                Some(Class::Comment)
+            } else if scss && self.eat("//") {
+                self.bump_while(|c| c != '\n');
+                Some(Class::Comment)
+            } else if c == '"' || c == '\'' {
+                self.bump();
+                self.bump_string(c, true);
+                Some(Class::String)
+            } else if c == '@' || c == '!' {
+                self.bump();
+                self.bump_while(is_word);
+                Some(Class::Keyword)
+            } else if c == '$' && scss {
+                self.bump();
+                self.bump_while(is_word);
+                Some(Class::Variable)
+            } else if c == '#' && depth > 0 {
+                self.bump();
+                self.bump_while(|c| c.is_ascii_alphanumeric());
+                Some(Class::Number)
+            } else if c.is_ascii_digit()
+                || ((c == '.' || c == '-') && self.peek_nth(1).is_some_and(|c| c.is_ascii_digit()))
+            {
+                self.bump();
+                self.bump_while(|c| c.is_alphanumeric() || c == '.' || c == '%');
+                Some(Class::Number)
+            } else if (c == '.' || c == '#') && self.peek_nth(1).is_some_and(is_word) {
+                self.bump();
+                self.bump_while(is_word);
+                Some(Class::Tag)
+            } else if is_word(c) {
+                self.bump_while(is_word);
+                if depth == 0 {
+                    Some(Class::Tag)
+                } else if self.peek_past_spaces() == Some(':') {
+                    Some(Class::Property)
+                } else if self.peek() == Some('(') {
+                    Some(Class::Function)
+                } else {
+                    None
+                }
+            } else {
+                match self.bump() {
+                    Some('{') => depth += 1,
+                    Some('}') => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                None
+            };
+            self.emit(start, class);
+        }
+    }
+
+    /// Scans the source as TOML.
+    fn scan_toml(&mut self) {
+        // Whether the next word is a key, and the depth of nested arrays and tables.
+        let mut expect_key = true;
+        let (mut arrays, mut tables) = (0usize, 0usize);
+        while let Some(c) = self.peek() {
+            let start = self.position;
+            let is_key = |c: char| c.is_alphanumeric() || "-_.".contains(c);
+            let class = if c == '#' {
+                self.bump_while(|c| c != '\n');
+                Some(Class::Comment)
+            } else if c == '[' && expect_key && tables == 0 {
+                self.bump_while(|c| c != ']' && c != '\n');
+                self.bump_while(|c| c == ']');
+                Some(Class::Tag)
+            } else if self.eat("\"\"\"") {
+                self.bump_past("\"\"\"");
+                Some(Class::String)
+            } else if self.eat("'''") {
+                self.bump_past("'''");
+                Some(Class::String)
+            } else if c == '"' || c == '\'' {
+                self.bump();
+                self.bump_string(c, c == '"');
+                if expect_key && self.peek_past_spaces() == Some('=') {
+                    Some(Class::Property)
+                } else {
+                    Some(Class::String)
+                }
+            } else if expect_key && is_key(c) {
+                self.bump_while(is_key);
+                Some(Class::Property)
+            } else if c.is_ascii_digit()
+                || ((c == '-' || c == '+') && self.peek_nth(1).is_some_and(|c| c.is_ascii_digit()))
+            {
+                self.bump();
+                self.bump_while(|c| c.is_alphanumeric() || "-+:._".contains(c));
+                Some(Class::Number)
+            } else if c.is_alphabetic() {
+                self.bump_while(char::is_alphanumeric);
+                match &self.source[start..self.position] {
+                    "true" | "false" | "inf" | "nan" => Some(Class::Keyword),
+                    _ => None,
+                }
+            } else {
+                self.bump();
+                match c {
+                    '=' => expect_key = false,
+                    '[' => arrays += 1,
+                    ']' => arrays = arrays.saturating_sub(1),
+                    '{' => {
+                        tables += 1;
+                        expect_key = true;
+                    }
+                    '}' => tables = tables.saturating_sub(1),
+                    ',' if tables > 0 && arrays == 0 => expect_key = true,
+                    '\n' if tables == 0 && arrays == 0 => expect_key = true,
+                    _ => {}
+                }
+                None
+            };
+            self.emit(start, class);
+        }
+    }
+
+    /// Scans the source as HTML, highlighting the contents
+    /// of `<script>` and `<style>` elements as JS and CSS.
+    fn scan_html(&mut self) {
+        while let Some(c) = self.peek() {
+            let start = self.position;
+            if self.eat("<!--") {
+                self.bump_past("-->");
+                self.emit(start, Some(Class::Comment));
+            } else if self.eat("<!") {
+                self.bump_past(">");
+                self.emit(start, Some(Class::Keyword));
+            } else if c == '<'
+                && self
+                    .peek_nth(1)
+                    .is_some_and(|c| c == '/' || c.is_alphabetic())
+            {
+                self.scan_html_tag();
+            } else {
+                self.bump();
+                self.bump_while(|c| c != '<');
+                self.emit(start, None);
+            }
+        }
+    }
+
+    /// Scans an HTML tag, and the contents of the element
+    /// if it's an opening `<script>` or `<style>` tag.
+    fn scan_html_tag(&mut self) {
+        let start = self.position;
+        self.bump();
+        let closing = self.eat("/");
+        let name_start = self.position;
+        self.bump_while(|c| c.is_alphanumeric() || c == '-' || c == ':');
+        let name = self.source[name_start..self.position].to_ascii_lowercase();
+        self.emit(start, Some(Class::Tag));
+
+        // Attributes.
+        let mut self_closing = false;
+        while let Some(c) = self.peek() {
+            let start = self.position;
+            if c == '>' || self.rest().starts_with("/>") {
+                self_closing = c == '/';
+                self.bump_past(">");
+                self.emit(start, Some(Class::Tag));
+                break;
+            } else if c == '"' || c == '\'' {
+                self.bump();
+                self.bump_string(c, false);
+                self.emit(start, Some(Class::String));
+            } else if c.is_whitespace() || c == '=' {
+                self.bump();
+                self.emit(start, None);
+            } else {
+                let is_value = self.source[..start].trim_end().ends_with('=');
+                self.bump_while(|c| !c.is_whitespace() && !"=>\"'".contains(c));
+                if self.position == start {
+                    self.bump();
+                }
+                let class = if is_value {
+                    Class::String
+                } else {
+                    Class::Property
+                };
+                self.emit(start, Some(class));
+            }
+        }
+
+        // Embedded scripts and styles.
+        let language = match name.as_str() {
+            "script" => Language::JavaScript,
+            "style" => Language::Css,
+            _ => return,
+        };
+        if closing || self_closing {
+            return;
+        }
+        let start = self.position;
+        let end = self
+            .rest()
+            .to_ascii_lowercase()
+            .find(&format!("</{}", name));
+        let end = end.map_or(self.source.len(), |end| start + end);
+        for (class, range) in tokenize(language, &self.source[start..end]) {
+            self.position = start + range.end;
+            self.emit(start + range.start, class);
+        }
+        self.position = end;
+    }
+}
+
+/// Returns the theme stylesheet for highlighted code blocks,
+/// as SCSS if `scss` is set or as CSS otherwise.
+///
+/// The CSS theme's colors are custom properties
+/// (like `--hl-keyword`) which may be overridden,
+/// while the SCSS theme's colors are `!default`
+/// variables (like `$hl-keyword`).
+pub fn stylesheet(scss: bool) -> String {
+    let mut colors: Vec<(&str, &str)> = Class::ALL
+        .iter()
+        .map(|class| (class.name(), class.color()))
+        .collect();
+    colors.push(("line-number", "#959da5"));
+    colors.push(("highlighted", "rgba(255, 220, 0, 0.2)"));
+    let color = |name: &str| match scss {
+        true => format!("$hl-{}", name),
+        false => format!("var(--hl-{})", name),
+    };
+
+    // Rules of the theme, as (selector, declarations) pairs.
+    let mut rules: Vec<(String, String)> = Class::ALL
+        .iter()
+        .map(|class| {
+            let mut declarations = format!("color: {};", color(class.name()));
+            if *class == Class::Comment {
+                declarations += " font-style: italic;";
+            }
+            (format!(" .hl-{}", class.name()), declarations)
+        })
+        .collect();
+    rules.push((
+        " .line".into(),
+        "display: inline-block; min-width: 100%;".into(),
+    ));
+    rules.push((
+        " .line.highlighted".into(),
+        format!("background: {};", color("highlighted")),
+    ));
+    rules.push((".line-numbers code".into(), "counter-reset: line;".into()));
+    rules.push((
+        ".line-numbers .line::before".into(),
+        format!(
+            "counter-increment: line; content: counter(line); display: inline-block; \
+             width: 2em; margin-right: 1em; text-align: right; color: {}; user-select: none;",
+            color("line-number")
+        ),
+    ));
+
+    let mut css = String::from("/* Syntax highlighting theme generated by aer. */\n");
+    if scss {
+        for (name, value) in &colors {
+            css += &format!("$hl-{}: {} !default;\n", name, value);
+        }
+        css += "\n.highlight {\n";
+        for (selector, declarations) in &rules {
+            let selector = match selector.strip_prefix(' ') {
+                Some(selector) => selector.to_string(),
+                None => format!("&{}", selector),
+            };
+            css += &format!("  {} {{ {} }}\n", selector, declarations);
+        }
+        css += "}\n";
+    } else {
+        css += "\n.highlight {\n";
+        for (name, value) in &colors {
+            css += &format!("  --hl-{}: {};\n", name, value);
+        }
+        css += "}\n";
+        for (selector, declarations) in &rules {
+            css += &format!(".highlight{} {{ {} }}\n", selector, declarations);
+        }
+    }
+    css
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the classified text of each token in `code`.
+    fn classes(language: &str, code: &str) -> Vec<(&'static str, String)> {
+        tokenize(Language::from_name(language).unwrap(), code)
+            .into_iter()
+            .filter_map(|(class, range)| Some((class?.name(), code[range].to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn highlights_rust() {
+        let code = "// Hi\nfn main() -> Result<(), &'a str> {\n    println!(\"{}\", r#\"\"#, 'x', 1.5e3);\n}";
+        assert_eq!(
+            classes("rust", code),
+            [
+                ("comment", "// Hi"),
+                ("keyword", "fn"),
+                ("function", "main"),
+                ("type", "Result"),
+                ("variable", "'a"),
+                ("type", "str"),
+                ("function", "println!"),
+                ("string", "\"{}\""),
+                ("string", "r#\"\"#"),
+                ("string", "'x'"),
+                ("number", "1.5e3"),
+            ]
+            .map(|(class, text)| (class, text.to_string()))
+        );
+    }
+
+    #[test]
+    fn highlights_toml() {
+        let code =
+            "[site]\ntitle = \"Hi\" # Comment\ntags = [\"a\", 1]\npoint = { x = 1.5, y = true }";
+        assert_eq!(
+            classes("toml", code),
+            [
+                ("tag", "[site]"),
+                ("property", "title"),
+                ("string", "\"Hi\""),
+                ("comment", "# Comment"),
+                ("property", "tags"),
+                ("string", "\"a\""),
+                ("number", "1"),
+                ("property", "point"),
+                ("property", "x"),
+                ("number", "1.5"),
+                ("property", "y"),
+                ("keyword", "true"),
+            ]
+            .map(|(class, text)| (class, text.to_string()))
+        );
+    }
+
+    #[test]
+    fn highlights_html() {
+        let code = "<!-- Hi -->\n<a href=\"/\" hidden>x</a><script>let y = 1;</script>";
+        assert_eq!(
+            classes("html", code),
+            [
+                ("comment", "<!-- Hi -->"),
+                ("tag", "<a"),
+                ("property", "href"),
+                ("string", "\"/\""),
+                ("property", "hidden"),
+                ("tag", ">"),
+                ("tag", "</a><script>"),
+                ("keyword", "let"),
+                ("number", "1"),
+                ("tag", "</script>"),
+            ]
+            .map(|(class, text)| (class, text.to_string()))
+        );
+    }
+
+    #[test]
+    fn highlights_scss() {
+        let code =
+            "$gap: 4px;\n.card:hover {\n  margin: $gap auto; // Hi\n  color: #fff !important;\n}";
+        assert_eq!(
+            classes("scss", code),
+            [
+                ("variable", "$gap"),
+                ("number", "4px"),
+                ("tag", ".card"),
+                ("tag", "hover"),
+                ("property", "margin"),
+                ("variable", "$gap"),
+                ("comment", "// Hi"),
+                ("property", "color"),
+                ("number", "#fff"),
+                ("keyword", "!important"),
+            ]
+            .map(|(class, text)| (class, text.to_string()))
+        );
+    }
+
+    #[test]
+    fn highlights_shell_and_glsl() {
+        assert_eq!(
+            classes("sh", "# Hi\nif [ \"$A\" ]; then echo $HOME '$x'; fi"),
+            [
+                ("comment", "# Hi"),
+                ("keyword", "if"),
+                ("string", "\"$A\""),
+                ("keyword", "then"),
+                ("variable", "$HOME"),
+                ("string", "'$x'"),
+                ("keyword", "fi"),
+            ]
+            .map(|(class, text)| (class, text.to_string()))
+        );
+        assert_eq!(
+            classes("glsl", "#version 300 es\nuniform vec3 color;"),
+            [
+                ("keyword", "#version 300 es"),
+                ("keyword", "uniform"),
+                ("type", "vec3"),
+            ]
+            .map(|(class, text)| (class, text.to_string()))
+        );
+    }
+
+    #[test]
+    fn highlights_lines() {
+        let code = "/* a\nb */\nlet x = \"<\";";
+        assert_eq!(
+            highlight(Some("js"), Some("{2-3}"), code, false),
+            "<span class=\"line\"><span class=\"hl-comment\">/* a</span></span>\n\
+             <span class=\"line highlighted\"><span class=\"hl-comment\">b */</span></span>\n\
+             <span class=\"line highlighted\"><span class=\"hl-keyword\">let</span> x = \
+             <span class=\"hl-string\">\"&lt;\"</span>;</span>"
+        );
+
+        // Unknown languages are escaped without highlighting.
+        assert_eq!(highlight(Some("text"), None, "a < b", false), "a &lt; b");
+        assert_eq!(
+            highlight(None, None, "a\nb", true),
+            "<span class=\"line\">a</span>\n<span class=\"line\">b</span>"
+        );
+    }
+
+    #[test]
+    fn parses_line_ranges() {
+        assert_eq!(line_ranges("{3-5}"), vec![3..=5]);
+        assert_eq!(line_ranges("title {1, 4-6}"), vec![1..=1, 4..=6]);
+        assert!(line_ranges("title").is_empty());
+    }
+
+    #[test]
+    fn generates_stylesheets() {
+        let css = stylesheet(false);
+        assert!(css.contains("--hl-keyword: #d73a49;"));
+        assert!(css.contains(".highlight .hl-keyword { color: var(--hl-keyword); }"));
+        assert!(css.contains(".highlight.line-numbers .line::before"));
+
+        let scss = stylesheet(true);
+        assert!(scss.contains("$hl-keyword: #d73a49 !default;"));
+        assert!(scss.contains("  .hl-keyword { color: $hl-keyword; }"));
+        assert!(scss.contains("  &.line-numbers .line::before"));
+    }
+}
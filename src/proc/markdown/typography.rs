@@ -0,0 +1,164 @@
+//! Smart punctuation for Markdown text.
+
+/// No-break space, placed before colons and inside guillemets in French.
+const NO_BREAK_SPACE: char = '\u{a0}';
+
+/// Narrow no-break space, placed before `;`, `!`, and `?` in French.
+const NARROW_NO_BREAK_SPACE: char = '\u{202f}';
+
+/// Punctuation applied to Markdown text.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Punctuation {
+    /// Text is left as written.
+    Plain,
+    /// `--` in text directly inside a paragraph becomes an em dash (`—`).
+    #[default]
+    Dashes,
+    /// Smart punctuation (see [smarten]) in the conventions of a locale.
+    Smart(Locale),
+}
+
+/// Typographic conventions of a locale.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Locale {
+    /// Curly quotes (`“…”` and `‘…’`).
+    #[default]
+    English,
+    /// Guillemets (`« … »`) and no-break
+    /// spaces before `;`, `:`, `!`, and `?`.
+    French,
+}
+
+impl Locale {
+    /// Returns the locale identified by `code`, like
+    /// `en` or `fr-CA`. Unknown locales are English.
+    pub fn from_code(code: &str) -> Self {
+        let language = code.split(['-', '_']).next().unwrap_or_default();
+        if language.eq_ignore_ascii_case("fr") {
+            Locale::French
+        } else {
+            Locale::English
+        }
+    }
+}
+
+/// Returns `text` with smart punctuation in the conventions of `locale`:
+///
+/// - `"` and `'` become opening or closing quotes (or apostrophes).
+/// - `...` becomes an ellipsis (`…`).
+/// - `---` becomes an em dash (`—`), as does `--`, unless it's
+///   between two digits (like `1--5`), where it's an en dash (`–`).
+///
+/// `previous` is the character preceding `text` in its block, if any,
+/// which determines whether quotes at the start of `text` open or close.
+pub(super) fn smarten(text: &str, previous: Option<char>, locale: Locale) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut smart = String::with_capacity(text.len());
+    let mut previous = previous;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            '.' if chars[i..].starts_with(&['.', '.', '.']) => {
+                smart.push('…');
+                i += 3;
+            }
+            '-' if chars[i..].starts_with(&['-', '-', '-']) => {
+                smart.push('—');
+                i += 3;
+            }
+            '-' if next == Some('-') => {
+                let between_digits = previous.is_some_and(|c| c.is_ascii_digit())
+                    && chars.get(i + 2).is_some_and(|c| c.is_ascii_digit());
+                smart.push(if between_digits { '–' } else { '—' });
+                i += 2;
+            }
+            '"' => {
+                match (locale, opens_quote(previous)) {
+                    (Locale::English, true) => smart.push('“'),
+                    (Locale::English, false) => smart.push('”'),
+                    (Locale::French, true) => smart.extend(['«', NO_BREAK_SPACE]),
+                    (Locale::French, false) => {
+                        no_break_before(&mut smart, NO_BREAK_SPACE, true);
+                        smart.push('»');
+                    }
+                }
+                i += 1;
+            }
+            '\'' => {
+                smart.push(if opens_quote(previous) { '‘' } else { '’' });
+                i += 1;
+            }
+            ';' | '!' | '?' | ':' if locale == Locale::French => {
+                let space = match c {
+                    ':' => NO_BREAK_SPACE,
+                    _ => NARROW_NO_BREAK_SPACE,
+                };
+                no_break_before(&mut smart, space, false);
+                smart.push(c);
+                i += 1;
+            }
+            c => {
+                smart.push(c);
+                i += 1;
+            }
+        }
+        previous = smart.chars().next_back();
+    }
+    smart
+}
+
+/// Returns true if a quote following
+/// `previous` opens a quotation.
+fn opens_quote(previous: Option<char>) -> bool {
+    match previous {
+        None => true,
+        Some(c) => c.is_whitespace() || "([{<—–-/“‘«".contains(c),
+    }
+}
+
+/// Replaces a space at the end of `smart` with the no-break `space`.
+/// If `always` is set, `space` is inserted if there's no space to replace.
+fn no_break_before(smart: &mut String, space: char, always: bool) {
+    if smart.ends_with(' ') {
+        smart.pop();
+        smart.push(space);
+    } else if always && !smart.ends_with(space) {
+        smart.push(space);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smartens_english() {
+        let cases = [
+            (r#""Hello," she said."#, "“Hello,” she said."),
+            ("It's 'quoted' (\"really\")", "It’s ‘quoted’ (“really”)"),
+            ("Wait...", "Wait…"),
+            ("Hello -- world --- again", "Hello — world — again"),
+            ("Pages 1--5", "Pages 1–5"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(smarten(text, None, Locale::English), expected);
+        }
+
+        // Quotes following preceding text close.
+        assert_eq!(smarten("\" now", Some('d'), Locale::English), "” now");
+        assert_eq!(smarten("\"now", Some(' '), Locale::English), "“now");
+    }
+
+    #[test]
+    fn smartens_french() {
+        assert_eq!(
+            smarten(r#"Il a dit "bonjour" : ça va ? Oui!"#, None, Locale::French),
+            "Il a dit «\u{a0}bonjour\u{a0}»\u{a0}: ça va\u{202f}? Oui!"
+        );
+        assert_eq!(Locale::from_code("fr-CA"), Locale::French);
+        assert_eq!(Locale::from_code("en"), Locale::English);
+        assert_eq!(Locale::from_code("de"), Locale::English);
+    }
+}
@@ -1,16 +1,27 @@
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use codas::types::Text;
-use logos::{Lexer, Logos, Span};
 
 use crate::proc::{
-    Asset, ContextValue, Environment, LayeredContext, MediaCategory, ProcessesAssets,
-    ProcessingError,
+    Asset, Context, ContextValue, Environment, LayeredContext, MediaCategory, MediaType,
+    ProcessesAssets, ProcessingError,
 };
-use crate::tool::procs::ASSET_PATH_CONTEXT_KEY_PREFIX;
-
+mod ast;
+mod condition;
+mod diagnostic;
+mod inheritance;
+mod macros;
+mod query;
 mod tokenizer;
 
-use tokenizer::{TemplateExpression, Token};
+pub use ast::TemplateCache;
+use std::sync::Arc;
+
+use ast::{Branch, Expression, Node, Template};
+use diagnostic::TemplateFile;
+pub use diagnostic::{TemplateDiagnostic, TemplateLocation};
+use inheritance::{Blocks, Parent};
+use query::LoopQuery;
+use tokenizer::{Filter, TemplateExpression};
 
 /// Prefix used to store parts in the processing context.
 pub const PART_CONTEXT_PREFIX: &str = "_part:";
@@ -18,6 +29,64 @@ pub const PART_CONTEXT_PREFIX: &str = "_part:";
 /// Prefix used to store part defaults in the processing context.
 pub const PART_DEFAULTS_PREFIX: &str = "_part_ctx:";
 
+/// Prefix used to store the number of lines of frontmatter
+/// preceding each part's content in the processing context.
+pub const PART_LINES_PREFIX: &str = "_part_lines:";
+
+/// Key used to store loop metadata in the scope of each loop iteration.
+const LOOP_CONTEXT_KEY: &str = "loop";
+
+/// Key used to store the content of each slot in the scope of a part.
+const SLOTS_CONTEXT_KEY: &str = "_slots";
+
+/// Key used to collect `fill` blocks in the scope of a `use` block.
+const FILLS_CONTEXT_KEY: &str = "_fills";
+
+/// Name of the slot containing a `use` block's content outside of any `fill`.
+const DEFAULT_SLOT_NAME: &str = "";
+
+/// Prefix used to store the path of the part imported
+/// as each macro namespace in the processing context.
+const MACROS_CONTEXT_PREFIX: &str = "_macros:";
+
+/// Returns the metadata of all assets in `dir_path` and its subdirectories,
+/// ordered by an optional `sort` clause (e.g., `"date desc"`), exactly
+/// as iterated by `{~ for item in assets "dir_path" sort date desc }`.
+pub fn query_assets(
+    env: &Environment,
+    context: &LayeredContext,
+    dir_path: &str,
+    sort: Option<&str>,
+) -> Result<Vec<ContextValue>, ProcessingError> {
+    let mut clauses = vec![];
+    if let Some(sort) = sort {
+        clauses.push(TemplateExpression::Identifier("sort".into()));
+        clauses.extend(
+            sort.split_whitespace()
+                .map(|word| TemplateExpression::Identifier(word.into())),
+        );
+    }
+
+    LoopQuery::parse(&clauses)?.apply(env, context, query::collect_assets(context, dir_path))
+}
+
+/// Converts `text` into a lowercase, hyphen-separated slug,
+/// exactly as filtered by `{~ get text | slugify }`.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    let mut last = None;
+    for char in text.chars() {
+        if char.is_alphanumeric() {
+            slug.extend(char.to_lowercase());
+            last = Some(char);
+        } else if last.is_some_and(|c| c != '-') {
+            slug.push('-');
+            last = Some('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
 /// Processes text assets containing template expressions wrapped in
 /// `{~ }`, drawing values from a context of key-value pairs.
 ///
@@ -47,7 +116,32 @@ pub const PART_DEFAULTS_PREFIX: &str = "_part_ctx:";
 ///    <li>Roy</li>
 /// </ul>
 /// ```
-pub struct TemplateProcessor;
+#[derive(Debug, Default)]
+pub struct TemplateProcessor {
+    /// Whether unresolved variables are errors.
+    strict: bool,
+
+    /// Whether the newline after each block expression is removed.
+    trim_blocks: bool,
+}
+
+impl TemplateProcessor {
+    /// Creates a new template processor.
+    ///
+    /// If `strict` is true, variables which can't be resolved in `get`,
+    /// `date`, `for`, and `with` expressions fail compilation, instead
+    /// of being output as-is (or skipped).
+    ///
+    /// If `trim_blocks` is true, the first newline after each expression
+    /// opening, splitting, or closing a block (like `if`, `else`, and `end`)
+    /// is removed from the output.
+    pub fn new(strict: bool, trim_blocks: bool) -> Self {
+        Self {
+            strict,
+            trim_blocks,
+        }
+    }
+}
 
 impl ProcessesAssets for TemplateProcessor {
     fn process(
@@ -63,9 +157,25 @@ impl ProcessesAssets for TemplateProcessor {
         tracing::trace!("template: {}", asset.path());
 
         let template = asset.as_text()?;
-        let mut lexer = Token::lexer(template);
+        let file = TemplateFile {
+            path: asset.path(),
+            source: template,
+            line_offset: 0,
+            // Values output into HTML (and Markdown, which
+            // compiles into HTML) assets are escaped by default.
+            escape_html: matches!(asset.media_type(), MediaType::Html | MediaType::Markdown),
+            in_tag: false,
+            strict: self.strict,
+            trim_blocks: self.trim_blocks,
+            blocks: None,
+            block: None,
+        };
+        let parsed = Self::parse(env, template, self.trim_blocks);
         let mut output = String::with_capacity(template.len());
-        Self::compile_template(env, context, &mut lexer, &mut output)?;
+
+        // Variables set by the template are scoped to the template.
+        let mut context = context.child_scope();
+        Self::render_template(env, &file, &mut context, &parsed, &mut output)?;
         asset.replace_with_text(output.into(), asset.media_type().clone());
 
         Ok(true)
@@ -73,610 +183,1229 @@ impl ProcessesAssets for TemplateProcessor {
 }
 
 impl TemplateProcessor {
-    /// Compiles a text template containing zero or more [TemplateExpression]s,
-    /// appending the compiled results to `output`.
-    fn compile_template(
+    /// Renders the parsed `template` of `file`, appending the rendered results
+    /// to `output`. If the template extends another template, the root of its
+    /// chain of parents is rendered instead, with each block overridden by
+    /// its most derived definition.
+    fn render_template(
         env: &Environment,
-        context: &LayeredContext,
-        lexer: &mut Lexer<Token>,
+        file: &TemplateFile,
+        context: &mut LayeredContext,
+        template: &Template,
         output: &mut String,
     ) -> Result<(), ProcessingError> {
-        while let Some(token) = lexer.next() {
-            match token {
-                // Evaluate the expression.
-                Ok(Token::OpenTemplate(Ok(TemplateExpression::Function {
-                    name, args, ..
-                }))) => {
-                    match name.as_str() {
-                        // Variable reference: {~ get variable_name }
-                        // Supports fallback chain: {~ get title or name or headline }
-                        "get" => {
-                            let identifier = args
-                                .first()
-                                .ok_or(ProcessingError::Compilation {
-                                    message: "missing variable identifier in variable reference"
-                                        .into(),
-                                })?
-                                .try_as_identifier()?;
-
-                            // Collect all identifiers in the fallback chain.
-                            let mut identifiers = vec![identifier];
-                            let mut i = 1;
-                            while i < args.len() {
-                                let keyword = args[i].try_as_identifier()?;
-                                if keyword != "or" {
-                                    return Err(ProcessingError::Compilation {
-                                        message: format!(
-                                            "expected 'or' in get expression, got '{}'",
-                                            keyword
-                                        )
-                                        .into(),
-                                    });
-                                }
-                                let next = args.get(i + 1).ok_or(ProcessingError::Compilation {
-                                    message: "missing variable identifier after 'or'".into(),
-                                })?;
-                                identifiers.push(next.try_as_identifier()?);
-                                i += 2;
-                            }
-
-                            // Try each identifier until one resolves.
-                            let mut resolved = None;
-                            for ident in &identifiers {
-                                match context.resolve(ident) {
-                                    Some(ContextValue::Text(text)) => {
-                                        resolved = Some(text.clone());
-                                        break;
-                                    }
-                                    Some(ContextValue::AssetRef(path)) => {
-                                        if let Some(output_path) =
-                                            env.asset_outputs.get(path.as_str())
-                                        {
-                                            resolved = Some(format!("/{}", output_path).into());
-                                        }
-                                        break;
-                                    }
-                                    Some(ContextValue::List(items)) => {
-                                        let mut s = String::from("[");
-                                        for (i, item) in items.iter().enumerate() {
-                                            if i > 0 {
-                                                s.push_str(", ");
-                                            }
-                                            match item {
-                                                ContextValue::Text(t) => s.push_str(t),
-                                                other => {
-                                                    s.push_str(&format!("{:?}", other));
-                                                }
-                                            }
-                                        }
-                                        s.push(']');
-                                        resolved = Some(s.into());
-                                        break;
-                                    }
-                                    _ => continue,
-                                }
-                            }
+        if Self::extended(template).is_none() {
+            return Self::render_nodes(env, file, context, &template.nodes, output);
+        }
 
-                            let value = resolved.unwrap_or_else(|| {
-                                let chain = identifiers
-                                    .iter()
-                                    .map(|id| id.as_str())
-                                    .collect::<Vec<_>>()
-                                    .join(" or ");
-                                format!("{{~ get {} }}", chain).into()
-                            });
+        // Load the templates extended by `template`, up to the root.
+        let mut parents: Vec<Parent> = vec![];
+        loop {
+            let (child_file, child) = match parents.last() {
+                Some(parent) => (parent.file(file), &*parent.template),
+                None => (*file, template),
+            };
+            let Some(expression) = Self::extended(child) else {
+                break;
+            };
+
+            let parent = Self::load_parent(env, context, file, &parents, expression)
+                .map_err(|error| child_file.diagnose(error, expression.span.clone()))?;
+            parents.push(parent);
+        }
 
-                            output.push_str(&value);
-                        }
+        let files: Vec<_> = std::iter::once((*file, template))
+            .chain(
+                parents
+                    .iter()
+                    .map(|parent| (parent.file(file), &*parent.template)),
+            )
+            .collect();
+        let blocks = Blocks::collect(files.iter().copied());
+
+        // Render everything outside the blocks of each extending template (like
+        // `set` expressions) for its effects on the context, discarding the output.
+        let (root_file, root) = files[files.len() - 1];
+        let mut discarded = String::new();
+        for (file, template) in &files[..files.len() - 1] {
+            for node in &template.nodes {
+                match node {
+                    Node::Text(_) => {}
+                    Node::Expression(expression)
+                        if expression.name == "extends" || expression.name == "block" => {}
+                    node => Self::render_nodes(
+                        env,
+                        file,
+                        context,
+                        std::slice::from_ref(node),
+                        &mut discarded,
+                    )?,
+                }
+            }
+        }
 
-                        // Date formatting: {~ date variable "format" }
-                        // Parses the variable as a date, then formats it
-                        // using a chrono strftime format string.
-                        "date" => {
-                            let identifier = args
-                                .first()
-                                .ok_or(ProcessingError::Compilation {
-                                    message: "missing variable identifier in date expression"
-                                        .into(),
-                                })?
-                                .try_as_identifier()?;
-
-                            let format = args
-                                .get(1)
-                                .ok_or(ProcessingError::Compilation {
-                                    message: "missing format string in date expression".into(),
-                                })?
-                                .try_as_string()?;
-
-                            if let Some(ContextValue::Text(raw)) = context.resolve(&identifier) {
-                                if let Some(date) = Self::parse_date(raw) {
-                                    output.push_str(&date.format(format.as_str()).to_string());
-                                } else {
-                                    output.push_str(raw);
-                                }
-                            }
-                        }
+        let root_file = TemplateFile {
+            blocks: Some(&blocks),
+            ..root_file
+        };
+        Self::render_nodes(env, &root_file, context, &root.nodes, output)
+    }
 
-                        // If statement:
-                        //   {~ if [not] condition } ... {~ end }
-                        //   {~ if var is [not] value } ... {~ end }
-                        "if" => {
-                            let first_arg = args
-                                .first()
-                                .ok_or(ProcessingError::Compilation {
-                                    message: "missing variable identifier in if expression".into(),
-                                })?
-                                .try_as_identifier()?;
-
-                            // Detect comparison form: {~ if var is [not] value }
-                            let is_comparison = args
-                                .get(1)
-                                .and_then(|a| a.try_as_identifier().ok())
-                                .is_some_and(|id| id == "is");
-
-                            let should_render = if is_comparison {
-                                let identifier = &first_arg;
-
-                                // Check for "not" after "is".
-                                let (negate, value_index) = if args
-                                    .get(2)
-                                    .and_then(|a| a.try_as_identifier().ok())
-                                    .is_some_and(|id| id == "not")
-                                {
-                                    (true, 3)
-                                } else {
-                                    (false, 2)
-                                };
+    /// Renders the macro called by `expression` (`{~ namespace.name args }`),
+    /// appending the rendered results to `output`.
+    ///
+    /// Like parts, macros are rendered in a scope of the caller's context,
+    /// into which their arguments and the top-level `import`s of the part
+    /// defining them are bound.
+    fn call_macro(
+        env: &Environment,
+        file: &TemplateFile,
+        context: &LayeredContext,
+        expression: &Expression,
+        output: &mut String,
+    ) -> Result<(), ProcessingError> {
+        let Some((namespace, name)) = expression.name.split_once('.') else {
+            unreachable!("macro calls are dotted");
+        };
+
+        let key: Text = format!("{}{}", MACROS_CONTEXT_PREFIX, namespace).into();
+        let Some(ContextValue::Text(path)) = context.get(&key) else {
+            return Err(ProcessingError::Compilation {
+                message: format!("unknown template function: {}", expression.name).into(),
+            });
+        };
+        let (source, line_offset) = Self::part(context, path)?;
+        let template = Self::parse(env, source, file.trim_blocks);
+
+        // Find the macro's definition at the top level of its part.
+        let Some(definition) = template.nodes.iter().find_map(|node| match node {
+            Node::Expression(definition)
+                if definition.name == "macro"
+                    && macros::name(&definition.args).is_ok_and(|id| id == name) =>
+            {
+                Some(definition)
+            }
+            _ => None,
+        }) else {
+            return Err(ProcessingError::Compilation {
+                message: format!("macro not found: {} in {}", name, path).into(),
+            });
+        };
+
+        let macro_file = TemplateFile {
+            path,
+            source,
+            line_offset,
+            blocks: None,
+            block: None,
+            ..*file
+        };
+        let mut scope = context.child_scope();
+        for node in &template.nodes {
+            if let Node::Expression(import) = node
+                && import.name == "import"
+            {
+                Self::render_nodes(
+                    env,
+                    &macro_file,
+                    &mut scope,
+                    std::slice::from_ref(node),
+                    output,
+                )
+                .map_err(|error| file.include(error, expression.span.clone()))?;
+            }
+        }
+        macros::bind(
+            env,
+            context,
+            &mut scope,
+            &definition.args,
+            &expression.args,
+            file.strict,
+        )?;
+
+        let nodes = Self::block_nodes(&definition.block)?;
+        Self::render_nodes(env, &macro_file, &mut scope, nodes, output)
+            .map_err(|error| file.include(error, expression.span.clone()))
+    }
+
+    /// Returns the top-level `extends` expression of `template`, if any.
+    fn extended(template: &Template) -> Option<&Expression> {
+        template.nodes.iter().find_map(|node| match node {
+            Node::Expression(expression) if expression.name == "extends" => Some(expression),
+            _ => None,
+        })
+    }
 
-                                let compare_arg =
-                                    args.get(value_index).ok_or(ProcessingError::Compilation {
-                                        message: "missing value in 'is' comparison".into(),
-                                    })?;
-
-                                // The right-hand side can be a string literal or
-                                // an identifier resolved against the context.
-                                let rhs = match compare_arg {
-                                    TemplateExpression::String(s) => Some(s.clone()),
-                                    TemplateExpression::Identifier(id) => {
-                                        match context.resolve(id) {
-                                            Some(ContextValue::Text(t)) => Some(t.clone()),
-                                            _ => None,
-                                        }
-                                    }
-                                    _ => None,
-                                };
+    /// Loads the template extended by the `extends` expression of
+    /// the last of `parents` (or of `file`, if there aren't any).
+    fn load_parent(
+        env: &Environment,
+        context: &LayeredContext,
+        file: &TemplateFile,
+        parents: &[Parent],
+        expression: &Expression,
+    ) -> Result<Parent, ProcessingError> {
+        let path = expression
+            .args
+            .first()
+            .ok_or(ProcessingError::Compilation {
+                message: "missing path in extends expression".into(),
+            })?
+            .try_as_string()?;
+
+        if path == file.path || parents.iter().any(|parent| parent.path == path) {
+            return Err(ProcessingError::Compilation {
+                message: format!("circular extends of {}", path).into(),
+            });
+        }
 
-                                let lhs = match context.resolve(identifier) {
-                                    Some(ContextValue::Text(t)) => Some(t.clone()),
-                                    _ => None,
-                                };
+        let (source, line_offset) = Self::part(context, &path)?;
+        Ok(Parent {
+            template: Self::parse(env, source, file.trim_blocks),
+            source: source.clone(),
+            line_offset,
+            path,
+        })
+    }
 
-                                let matches = lhs.is_some() && lhs == rhs;
-                                if negate { !matches } else { matches }
-                            } else {
-                                // Truthiness form: {~ if [not] condition }
-                                let (negate, identifier) = if first_arg.as_str() == "not" {
-                                    let second_arg = args
-                                        .get(1)
-                                        .ok_or(ProcessingError::Compilation {
-                                            message: "missing variable identifier after 'not'"
-                                                .into(),
-                                        })?
-                                        .try_as_identifier()?;
-                                    (true, second_arg)
-                                } else {
-                                    (false, first_arg)
-                                };
+    /// Renders the parsed template `nodes` of `file`,
+    /// appending the rendered results to `output`.
+    fn render_nodes(
+        env: &Environment,
+        file: &TemplateFile,
+        context: &mut LayeredContext,
+        nodes: &[Node],
+        output: &mut String,
+    ) -> Result<(), ProcessingError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Expression(expression) => {
+                    Self::render_expression(env, file, context, expression, output)
+                        .map_err(|error| file.diagnose(error, expression.span.clone()))?;
+                }
+                Node::Error { span, message } => {
+                    return Err(file.diagnose(
+                        ProcessingError::Compilation {
+                            message: message.clone(),
+                        },
+                        span.clone(),
+                    ));
+                }
+            }
+        }
 
-                                let truthy = match context.resolve(identifier.as_str()) {
-                                    Some(ContextValue::Text(text)) => {
-                                        text != "false" && text != "0" && !text.is_empty()
-                                    }
-                                    Some(ContextValue::AssetRef(_)) => true,
-                                    Some(ContextValue::List(list)) => !list.is_empty(),
-                                    Some(ContextValue::Table(table)) => !table.is_empty(),
-                                    None => false,
-                                };
+        Ok(())
+    }
 
-                                if negate { !truthy } else { truthy }
-                            };
+    /// Renders the template `expression` (and the block it opens, if
+    /// any) of `file`, appending the rendered results to `output`.
+    fn render_expression(
+        env: &Environment,
+        file: &TemplateFile,
+        context: &mut LayeredContext,
+        expression: &Expression,
+        output: &mut String,
+    ) -> Result<(), ProcessingError> {
+        let Expression {
+            span,
+            name,
+            args,
+            block,
+        } = expression;
+
+        match name.as_str() {
+            // Variable reference: {~ get variable_name }
+            // Supports fallback chain: {~ get title or name or headline }
+            // Supports filters: {~ get title or name | upper | truncate 60 }
+            //
+            // In HTML assets, output is escaped unless it's
+            // referenced via `raw` or passed through a `raw`
+            // (or `escape`) filter: {~ raw content }
+            "get" | "raw" => {
+                // Filters on the last identifier apply to the whole chain.
+                let (args, filters) = Self::split_filters(args);
+
+                let identifier = args
+                    .first()
+                    .ok_or(ProcessingError::Compilation {
+                        message: "missing variable identifier in variable reference".into(),
+                    })?
+                    .try_as_identifier()?;
+
+                // Collect all identifiers in the fallback chain.
+                let mut identifiers = vec![identifier];
+                let mut i = 1;
+                while i < args.len() {
+                    let keyword = args[i].try_as_identifier()?;
+                    if keyword != "or" {
+                        return Err(ProcessingError::Compilation {
+                            message: format!("expected 'or' in get expression, got '{}'", keyword)
+                                .into(),
+                        });
+                    }
+                    let next = args.get(i + 1).ok_or(ProcessingError::Compilation {
+                        message: "missing variable identifier after 'or'".into(),
+                    })?;
+                    identifiers.push(next.try_as_identifier()?);
+                    i += 2;
+                }
 
-                            // If the condition passes, compile the contents of the block.
-                            let block_span: std::ops::Range<usize> =
-                                Self::traverse_template_block(lexer)?;
-                            if should_render {
-                                let block_text = &lexer.source()[block_span];
-                                let mut block_lexer = Token::lexer(block_text);
-                                Self::compile_template(env, context, &mut block_lexer, output)?;
-                            }
+                // Try each identifier until one resolves.
+                let mut resolved = None;
+                for ident in &identifiers {
+                    match context.resolve(ident) {
+                        Some(ContextValue::Table(_)) | None => continue,
+                        Some(value) => {
+                            resolved = Some(value.clone());
+                            break;
                         }
+                    }
+                }
 
-                        // Use statement:
-                        //   {~ use "path/to/part" }
-                        //   {~ use "path", with "Value" as key, with var as key2 }
-                        "use" => {
-                            let path = args
-                                .first()
-                                .ok_or(ProcessingError::Compilation {
-                                    message: "missing path in use expression".into(),
-                                })?
-                                .try_as_string()?;
-
-                            // Look up the part in the context.
-                            let part_key: Text = format!("{}{}", PART_CONTEXT_PREFIX, path).into();
-                            let part_content = match context.get(&part_key) {
-                                Some(ContextValue::Text(content)) => content,
-                                _ => {
-                                    return Err(ProcessingError::Compilation {
-                                        message: format!("part not found: {}", path).into(),
-                                    });
-                                }
-                            };
-
-                            // Merge part defaults into a scoped context.
-                            let mut part_context = context.child_scope();
-                            let defaults_key: Text =
-                                format!("{}{}", PART_DEFAULTS_PREFIX, path).into();
-                            if let Some(ContextValue::Table(defaults)) = context.get(&defaults_key)
-                            {
-                                part_context.extend_top(defaults.clone());
-                            }
+                let value = Self::apply_filters(env, resolved, filters)?
+                    .and_then(|value| Self::render_value(env, &value));
+                let value = match value {
+                    Some(value) => value,
+                    None => {
+                        let chain = identifiers
+                            .iter()
+                            .map(|id| id.as_str())
+                            .collect::<Vec<_>>()
+                            .join(" or ");
+                        if file.strict {
+                            return Err(Self::undefined_variable(&chain));
+                        }
+                        format!("{{~ {} {} }}", name, chain).into()
+                    }
+                };
 
-                            // Parse `with <value> as <key>` clauses.
-                            let mut i = 1;
-                            while i < args.len() {
-                                let keyword = args[i].try_as_identifier()?;
-                                if keyword != "with" {
-                                    return Err(ProcessingError::Compilation {
-                                        message: format!(
-                                            "expected 'with' in use expression, got '{}'",
-                                            keyword
-                                        )
-                                        .into(),
-                                    });
-                                }
-
-                                let value_arg =
-                                    args.get(i + 1).ok_or(ProcessingError::Compilation {
-                                        message: "missing value after 'with' in use expression"
-                                            .into(),
-                                    })?;
-                                let value = match value_arg {
-                                    TemplateExpression::String(s) => ContextValue::Text(s.clone()),
-                                    TemplateExpression::Identifier(id) => {
-                                        match context.resolve(id) {
-                                            Some(v) => v.clone(),
-                                            None => ContextValue::Text("".into()),
-                                        }
-                                    }
-                                    _ => {
-                                        return Err(ProcessingError::Compilation {
-                                            message: "invalid value in 'with' clause".into(),
-                                        });
-                                    }
-                                };
+                if name == "raw" || Self::is_trusted(filters) {
+                    output.push_str(&value);
+                } else {
+                    Self::push_escaped(file, output, &value);
+                }
+            }
 
-                                let as_keyword = args
-                                    .get(i + 2)
-                                    .ok_or(ProcessingError::Compilation {
-                                        message: "missing 'as' in 'with' clause".into(),
-                                    })?
-                                    .try_as_identifier()?;
-                                if as_keyword != "as" {
-                                    return Err(ProcessingError::Compilation {
-                                        message: format!(
-                                            "expected 'as' in 'with' clause, got '{}'",
-                                            as_keyword
-                                        )
-                                        .into(),
-                                    });
-                                }
-
-                                let key = args
-                                    .get(i + 3)
-                                    .ok_or(ProcessingError::Compilation {
-                                        message: "missing key after 'as' in 'with' clause".into(),
-                                    })?
-                                    .try_as_identifier()?;
-
-                                part_context.insert(key, value);
-                                i += 4;
-                            }
+            // Date formatting: {~ date variable "format" }
+            // Parses the variable as a date, then formats it
+            // using a chrono strftime format string.
+            // Supports filters: {~ date variable "format" | upper }
+            "date" => {
+                let (args, filters) = Self::split_filters(args);
+
+                let identifier = args
+                    .first()
+                    .ok_or(ProcessingError::Compilation {
+                        message: "missing variable identifier in date expression".into(),
+                    })?
+                    .try_as_identifier()?;
+
+                let format = args
+                    .get(1)
+                    .ok_or(ProcessingError::Compilation {
+                        message: "missing format string in date expression".into(),
+                    })?
+                    .try_as_string()?;
+
+                let formatted =
+                    context
+                        .resolve(&identifier)
+                        .and_then(|value| match Self::value_date(value) {
+                            Some(date) => Some(ContextValue::Text(
+                                date.format(format.as_str()).to_string().into(),
+                            )),
+                            None => value.to_text().map(ContextValue::Text),
+                        });
+
+                match Self::apply_filters(env, formatted, filters)?
+                    .and_then(|value| Self::render_value(env, &value))
+                {
+                    Some(value) if Self::is_trusted(filters) => output.push_str(&value),
+                    Some(value) => Self::push_escaped(file, output, &value),
+                    None if file.strict => {
+                        return Err(Self::undefined_variable(&identifier));
+                    }
+                    None => {}
+                }
+            }
 
-                            // Compile the part content with the merged context.
-                            let mut part_lexer = Token::lexer(part_content);
-                            Self::compile_template(env, &part_context, &mut part_lexer, output)?;
+            // Assignment: {~ set name = value }
+            // Supports fallback chain: {~ set heading = title or name or "Home" }
+            // Supports filters: {~ set slug = title | slugify }
+            "set" => {
+                let (name, value) = match args.as_slice() {
+                    [
+                        TemplateExpression::Identifier(name),
+                        TemplateExpression::Operator(operator),
+                        value @ ..,
+                    ] if operator == "=" && !value.is_empty() => (name, value),
+                    _ => {
+                        return Err(ProcessingError::Compilation {
+                            message: "expected 'name = value' in set expression".into(),
+                        });
+                    }
+                };
+                Self::check_variable_name(name)?;
+
+                // Filters on the last value apply to the whole chain.
+                let (value, filters) = Self::split_filters(value);
+
+                // Take the first value in the chain that resolves.
+                let mut resolved = None;
+                for (i, arg) in value.iter().enumerate() {
+                    if i % 2 == 1 {
+                        let keyword = arg.try_as_identifier()?;
+                        if keyword != "or" {
+                            return Err(ProcessingError::Compilation {
+                                message: format!(
+                                    "expected 'or' in set expression, got '{}'",
+                                    keyword
+                                )
+                                .into(),
+                            });
                         }
+                    } else if resolved.is_none() {
+                        resolved = Self::evaluate_value(env, context, arg)?;
+                    }
+                }
+                if value.len() % 2 == 0 {
+                    return Err(ProcessingError::Compilation {
+                        message: "missing value after 'or' in set expression".into(),
+                    });
+                }
 
-                        // For loop:
-                        //   {~ for item in collection } ... {~ end }
-                        //   {~ for key, val in table } ... {~ end }
-                        //   {~ for item in assets "path" } ... {~ end }
-                        "for" => {
-                            let first = args
-                                .first()
-                                .ok_or(ProcessingError::Compilation {
-                                    message: "missing item identifier in for loop".into(),
-                                })?
-                                .try_as_identifier()?;
-
-                            // Detect 4-arg form: key, val, in, table
-                            let is_kv_form = args.len() == 4
-                                && args
-                                    .get(2)
-                                    .and_then(|a| a.try_as_identifier().ok())
-                                    .is_some_and(|id| id == "in");
-
-                            // Detect assets query form: item, in, assets, "path" [sort key [asc|desc]]
-                            let is_assets_query = !is_kv_form
-                                && args.len() >= 4
-                                && args
-                                    .get(1)
-                                    .and_then(|a| a.try_as_identifier().ok())
-                                    .is_some_and(|id| id == "in")
-                                && args
-                                    .get(2)
-                                    .and_then(|a| a.try_as_identifier().ok())
-                                    .is_some_and(|id| id == "assets")
-                                && matches!(args.get(3), Some(TemplateExpression::String(_)));
-
-                            let block_span = Self::traverse_template_block(lexer)?;
-
-                            // Table iteration: {~ for key, val in table }
-                            if is_kv_form {
-                                let key_identifier = first;
-                                let val_identifier = args[1].try_as_identifier()?;
-                                let table_identifier = args[3].try_as_identifier()?;
-                                let resolved = context.resolve(&table_identifier);
-
-                                if let Some(ContextValue::Table(table)) = resolved
-                                    && !table.is_empty()
-                                {
-                                    let block_text = &lexer.source()[block_span];
-
-                                    for (k, v) in table {
-                                        let mut loop_context = context.child_scope();
-                                        loop_context.insert(
-                                            key_identifier.clone(),
-                                            ContextValue::Text(k.clone()),
-                                        );
-                                        loop_context.insert(val_identifier.clone(), v.clone());
-
-                                        let mut block_lexer = Token::lexer(block_text);
-                                        Self::compile_template(
-                                            env,
-                                            &loop_context,
-                                            &mut block_lexer,
-                                            output,
-                                        )?;
-                                    }
-                                }
-
-                            // Path query:
-                            //   {~ for item in assets "path" [sort key [asc|desc]] }
-                            } else if is_assets_query {
-                                let item_identifier = first;
-                                let dir_path = args[3].try_as_string()?;
-                                let assets_key: Text =
-                                    format!("{}{}", ASSET_PATH_CONTEXT_KEY_PREFIX, dir_path).into();
-
-                                // Parse optional sort clause.
-                                let sort_clause = if args.len() >= 6
-                                    && args
-                                        .get(4)
-                                        .and_then(|a| a.try_as_identifier().ok())
-                                        .is_some_and(|id| id == "sort")
-                                {
-                                    let sort_key = args[5].try_as_identifier()?;
-                                    let descending = args
-                                        .get(6)
-                                        .and_then(|a| a.try_as_identifier().ok())
-                                        .is_some_and(|id| id == "desc");
-                                    Some((sort_key, descending))
-                                } else {
-                                    None
-                                };
+                let resolved = Self::apply_filters(env, resolved, filters)?
+                    .unwrap_or(ContextValue::Text("".into()));
+                context.insert(name.clone(), resolved);
+            }
+
+            // Slot: {~ slot [name] }
+            // Outputs content passed to a part used as a block,
+            // or the content of the block's `fill name` block.
+            "slot" => {
+                let name = match args.first() {
+                    Some(arg) => arg.try_as_identifier()?,
+                    None => DEFAULT_SLOT_NAME.into(),
+                };
+
+                if let Some(ContextValue::Table(slots)) = context.get(&SLOTS_CONTEXT_KEY.into())
+                    && let Some(ContextValue::Text(content)) = slots.get(&name)
+                {
+                    output.push_str(content);
+                }
+            }
 
-                                // Collect items from the exact path and all
-                                // subdirectories (e.g., "logs" also gathers
-                                // from "logs/ldjam-57", "logs/guide-to-ai", etc.).
-                                let prefix: Text =
-                                    format!("{}{}/", ASSET_PATH_CONTEXT_KEY_PREFIX, dir_path)
-                                        .into();
-                                let mut all_items: Vec<ContextValue> = Vec::new();
-
-                                for (key, value) in
-                                    context.iter_by_prefix(ASSET_PATH_CONTEXT_KEY_PREFIX)
-                                {
-                                    if (*key == assets_key || key.starts_with(prefix.as_str()))
-                                        && let ContextValue::List(items) = value
-                                    {
-                                        all_items.extend(items.iter().cloned());
-                                    }
-                                }
-
-                                if !all_items.is_empty() {
-                                    let block_text = &lexer.source()[block_span];
-
-                                    let items = if let Some((sort_key, descending)) = &sort_clause {
-                                        // Schwartzian transform: parse sort keys
-                                        // once rather than on every comparison.
-                                        let mut keyed: Vec<_> = all_items
-                                            .into_iter()
-                                            .map(|item| {
-                                                let raw = Self::extract_sort_value(&item, sort_key);
-                                                let parsed =
-                                                    raw.as_deref().and_then(Self::parse_date);
-                                                (parsed, raw, item)
-                                            })
-                                            .collect();
-                                        keyed.sort_by(|(ad, ar, _), (bd, br, _)| {
-                                            let cmp = match (ad, bd) {
-                                                (Some(a), Some(b)) => a.cmp(b),
-                                                (Some(_), None) => std::cmp::Ordering::Less,
-                                                (None, Some(_)) => std::cmp::Ordering::Greater,
-                                                (None, None) => ar.cmp(br),
-                                            };
-                                            if *descending { cmp.reverse() } else { cmp }
-                                        });
-                                        keyed.into_iter().map(|(_, _, item)| item).collect()
-                                    } else {
-                                        all_items
-                                    };
-
-                                    for item in &items {
-                                        let mut loop_context = context.child_scope();
-                                        loop_context.insert(item_identifier.clone(), item.clone());
-
-                                        let mut block_lexer = Token::lexer(block_text);
-                                        Self::compile_template(
-                                            env,
-                                            &loop_context,
-                                            &mut block_lexer,
-                                            output,
-                                        )?;
-                                    }
-                                }
-
-                            // List iteration: {~ for item in collection }
-                            } else {
-                                let item_identifier = first;
-                                let collection_identifier = args
-                                    .get(2)
-                                    .ok_or(ProcessingError::Compilation {
-                                        message: "missing collection identifier in for loop".into(),
-                                    })?
-                                    .try_as_identifier()?;
-                                let collection = context.resolve(&collection_identifier);
-
-                                if let Some(ContextValue::List(items)) = collection
-                                    && !items.is_empty()
-                                {
-                                    let block_text = &lexer.source()[block_span];
-
-                                    for item in items {
-                                        let mut loop_context = context.child_scope();
-                                        loop_context.insert(item_identifier.clone(), item.clone());
-
-                                        let mut block_lexer = Token::lexer(block_text);
-                                        Self::compile_template(
-                                            env,
-                                            &loop_context,
-                                            &mut block_lexer,
-                                            output,
-                                        )?;
-                                    }
-                                }
+            // Slot fill: {~ fill name } ... {~ end }
+            // Renders the block into a named slot of the part
+            // whose block contains it.
+            "fill" => {
+                let name = args
+                    .first()
+                    .ok_or(ProcessingError::Compilation {
+                        message: "missing slot name in fill expression".into(),
+                    })?
+                    .try_as_identifier()?;
+
+                let nodes = Self::block_nodes(block)?;
+                let mut filled = String::new();
+                let nested_file = TemplateFile {
+                    in_tag: Self::ends_in_tag(file, output),
+                    ..*file
+                };
+                Self::render_nodes(env, &nested_file, context, nodes, &mut filled)?;
+
+                let Some(ContextValue::Table(fills)) = context.get(&FILLS_CONTEXT_KEY.into())
+                else {
+                    return Err(ProcessingError::Compilation {
+                        message: "unexpected 'fill' outside of a use block".into(),
+                    });
+                };
+                let mut fills = fills.clone();
+                fills.insert(name, ContextValue::Text(filled.into()));
+                context.insert(FILLS_CONTEXT_KEY.into(), ContextValue::Table(fills));
+            }
+
+            // Captured block: {~ capture name } ... {~ end }
+            // Renders the block into a text variable.
+            "capture" => {
+                let name = args
+                    .first()
+                    .ok_or(ProcessingError::Compilation {
+                        message: "missing variable name in capture expression".into(),
+                    })?
+                    .try_as_identifier()?;
+                Self::check_variable_name(&name)?;
+
+                let nodes = Self::block_nodes(block)?;
+                let mut captured = String::new();
+                let nested_file = TemplateFile {
+                    in_tag: Self::ends_in_tag(file, output),
+                    ..*file
+                };
+                // Variables set inside the block are local to it.
+                let mut block_context = context.child_scope();
+                Self::render_nodes(env, &nested_file, &mut block_context, nodes, &mut captured)?;
+
+                context.insert(name, ContextValue::Text(captured.into()));
+            }
+
+            // If statement:
+            //   {~ if [not] condition } ... {~ end }
+            //   {~ if var is [not] value } ... {~ end }
+            //   {~ if a } ... {~ else if b } ... {~ else } ... {~ end }
+            "if" => {
+                // Render the first branch whose condition passes;
+                // the opening `if` guards the first branch, and each
+                // `else [if ...]` clause guards the branch following it.
+                for (i, branch) in block.iter().flatten().enumerate() {
+                    let should_render = if i == 0 {
+                        condition::evaluate(env, context, args)?
+                    } else {
+                        match branch.clause.split_first() {
+                            None => true,
+                            Some((keyword, condition)) if keyword.try_as_identifier()? == "if" => {
+                                condition::evaluate(env, context, condition)?
+                            }
+                            Some((keyword, _)) => {
+                                return Err(ProcessingError::Compilation {
+                                    message: format!(
+                                        "expected 'if' after 'else', got {:?}",
+                                        keyword
+                                    )
+                                    .into(),
+                                });
                             }
                         }
+                    };
 
-                        // Valid end-of-block statements should be handled by
-                        // the block traversal logic above.
-                        "end" => {
-                            return Err(ProcessingError::Compilation {
-                                message: "unexpected end-of-block".into(),
-                            });
-                        }
+                    if should_render {
+                        Self::render_nodes(env, file, context, &branch.nodes, output)?;
+                        break;
+                    }
+                }
+            }
+
+            // Use statement:
+            //   {~ use "path/to/part" }
+            //   {~ use "path", with "Value" as key, with var as key2 }
+            //
+            // Parts may also be used as blocks, whose content
+            // is rendered into the part's slots:
+            //   {~ use "path/to/part" } ... {~ fill name } ... {~ end } ... {~ end }
+            "use" => {
+                let path = args
+                    .first()
+                    .ok_or(ProcessingError::Compilation {
+                        message: "missing path in use expression".into(),
+                    })?
+                    .try_as_string()?;
+
+                let (part_content, line_offset) = Self::part(context, &path)?;
+
+                // Merge part defaults into a scoped context.
+                let mut part_context = context.child_scope();
+                let defaults_key: Text = format!("{}{}", PART_DEFAULTS_PREFIX, path).into();
+                if let Some(ContextValue::Table(defaults)) = context.get(&defaults_key) {
+                    part_context.extend_top(defaults.clone());
+                }
+
+                // Parse `with <value> as <key>` clauses.
+                let mut i = 1;
+                while i < args.len() {
+                    let keyword = args[i].try_as_identifier()?;
+                    if keyword != "with" {
+                        return Err(ProcessingError::Compilation {
+                            message: format!(
+                                "expected 'with' in use expression, got '{}'",
+                                keyword
+                            )
+                            .into(),
+                        });
+                    }
 
-                        // Unknown template function.
-                        _ => {
-                            let message = format!("unknown template function: {}", name);
+                    let value_arg = args.get(i + 1).ok_or(ProcessingError::Compilation {
+                        message: "missing value after 'with' in use expression".into(),
+                    })?;
+                    let value = match value_arg {
+                        TemplateExpression::Function { .. } => {
                             return Err(ProcessingError::Compilation {
-                                message: message.into(),
+                                message: "invalid value in 'with' clause".into(),
                             });
                         }
+                        value_arg => match Self::evaluate_value(env, context, value_arg)? {
+                            Some(value) => value,
+                            None if file.strict => {
+                                let identifier = match value_arg {
+                                    TemplateExpression::Filtered { value, .. } => value,
+                                    value_arg => value_arg,
+                                };
+                                return Err(Self::undefined_variable(
+                                    &identifier.try_as_identifier()?,
+                                ));
+                            }
+                            None => ContextValue::Text("".into()),
+                        },
+                    };
+
+                    let as_keyword = args
+                        .get(i + 2)
+                        .ok_or(ProcessingError::Compilation {
+                            message: "missing 'as' in 'with' clause".into(),
+                        })?
+                        .try_as_identifier()?;
+                    if as_keyword != "as" {
+                        return Err(ProcessingError::Compilation {
+                            message: format!(
+                                "expected 'as' in 'with' clause, got '{}'",
+                                as_keyword
+                            )
+                            .into(),
+                        });
                     }
+
+                    let key = args
+                        .get(i + 3)
+                        .ok_or(ProcessingError::Compilation {
+                            message: "missing key after 'as' in 'with' clause".into(),
+                        })?
+                        .try_as_identifier()?;
+
+                    part_context.insert(key, value);
+                    i += 4;
                 }
 
-                // Unexpected template expression error.
-                Ok(Token::OpenTemplate(Ok(expression))) => {
-                    let message = format!("unexpected template expression: {:?}", expression);
-                    return Err(ProcessingError::Compilation {
-                        message: message.into(),
-                    });
+                // Render the block's content (if any) in the caller's
+                // scope, collecting it and any fills into the part's slots.
+                let mut slots = Context::new();
+                if block.is_some() {
+                    let nodes = Self::block_nodes(block)?;
+                    let mut block_context = context.child_scope();
+                    block_context.insert(
+                        FILLS_CONTEXT_KEY.into(),
+                        ContextValue::Table(Context::new()),
+                    );
+                    let mut default_slot = String::new();
+                    let nested_file = TemplateFile {
+                        in_tag: Self::ends_in_tag(file, output),
+                        ..*file
+                    };
+                    Self::render_nodes(
+                        env,
+                        &nested_file,
+                        &mut block_context,
+                        nodes,
+                        &mut default_slot,
+                    )?;
+
+                    if let Some(ContextValue::Table(fills)) =
+                        block_context.get(&FILLS_CONTEXT_KEY.into())
+                    {
+                        slots.extend(fills.clone());
+                    }
+                    slots.insert(
+                        DEFAULT_SLOT_NAME.into(),
+                        ContextValue::Text(default_slot.into()),
+                    );
                 }
+                part_context.insert(SLOTS_CONTEXT_KEY.into(), ContextValue::Table(slots));
+
+                // Render the part content with the merged context.
+                let part_file = TemplateFile {
+                    path: &path,
+                    source: part_content,
+                    line_offset,
+                    blocks: None,
+                    block: None,
+                    ..*file
+                };
+                let part = Self::parse(env, part_content, file.trim_blocks);
+                Self::render_template(env, &part_file, &mut part_context, &part, output)
+                    .map_err(|error| file.include(error, span.clone()))?;
+            }
+
+            // Overridable block: {~ block name } ... {~ end }
+            // Renders the most derived definition of the block
+            // in the chain of templates extending each other.
+            "block" => {
+                let name = args
+                    .first()
+                    .ok_or(ProcessingError::Compilation {
+                        message: "missing block name in block expression".into(),
+                    })?
+                    .try_as_identifier()?;
+                let nodes = Self::block_nodes(block)?;
+
+                match file.blocks {
+                    Some(blocks) => blocks.render(env, context, &name, 0, output)?,
+                    None => Self::render_nodes(env, file, context, nodes, output)?,
+                }
+            }
 
-                // Abort processing if the template contains any errors.
-                Ok(Token::OpenTemplate(Err(err))) => {
+            // Parent block: {~ super }
+            // Renders the definition of the enclosing block which
+            // the definition containing this expression overrides.
+            "super" => {
+                let (Some(blocks), Some((name, index))) = (file.blocks, file.block) else {
                     return Err(ProcessingError::Compilation {
-                        message: format!("template parse error: {}", err).into(),
+                        message: "unexpected 'super' outside of a block".into(),
                     });
-                }
+                };
+                blocks.render(env, context, name, index + 1, output)?;
+            }
+
+            // Macro definition: {~ macro name(param, param = default) } ... {~ end }
+            // Renders nothing; macros are rendered where they're called.
+            "macro" => {
+                macros::name(args)?;
+            }
 
-                // If the lexer couldn't parse a token, the next value
-                // is just text we can copy directly into the compiled template.
-                Err(_) => {
-                    let text = lexer.slice();
-                    output.push_str(text);
+            // Macro import: {~ import "path/to/part" as namespace }
+            // Makes the macros defined in a part callable
+            // in the current scope as `{~ namespace.name args }`.
+            "import" => {
+                let (path, namespace) = match args.as_slice() {
+                    [
+                        TemplateExpression::String(path),
+                        TemplateExpression::Identifier(keyword),
+                        TemplateExpression::Identifier(namespace),
+                    ] if keyword == "as" => (path, namespace),
+                    _ => {
+                        return Err(ProcessingError::Compilation {
+                            message: "expected '\"path\" as name' in import expression".into(),
+                        });
+                    }
+                };
+                Self::check_variable_name(namespace)?;
+                Self::part(context, path)?;
+
+                let key: Text = format!("{}{}", MACROS_CONTEXT_PREFIX, namespace).into();
+                context.insert(key, ContextValue::Text(path.clone()));
+            }
+
+            // Valid `extends` expressions are handled
+            // by the template inheritance logic.
+            "extends" => {
+                return Err(ProcessingError::Compilation {
+                    message: "unexpected 'extends' outside of the top level of a template".into(),
+                });
+            }
+
+            // For loop:
+            //   {~ for item in collection [clauses] } ... {~ end }
+            //   {~ for key, val in table } ... {~ end }
+            //   {~ for item in assets "path" [clauses] } ... {~ end }
+            //
+            // See [LoopQuery] for the supported clauses.
+            "for" => {
+                let first = args
+                    .first()
+                    .ok_or(ProcessingError::Compilation {
+                        message: "missing item identifier in for loop".into(),
+                    })?
+                    .try_as_identifier()?;
+
+                // Detect 4-arg form: key, val, in, table
+                let is_kv_form = args.len() == 4
+                    && args
+                        .get(2)
+                        .and_then(|a| a.try_as_identifier().ok())
+                        .is_some_and(|id| id == "in");
+
+                // Detect assets query form: item, in, assets, "path" [clauses]
+                let is_assets_query = !is_kv_form
+                    && args.len() >= 4
+                    && args
+                        .get(1)
+                        .and_then(|a| a.try_as_identifier().ok())
+                        .is_some_and(|id| id == "in")
+                    && args
+                        .get(2)
+                        .and_then(|a| a.try_as_identifier().ok())
+                        .is_some_and(|id| id == "assets")
+                    && matches!(args.get(3), Some(TemplateExpression::String(_)));
+
+                let nodes = Self::block_nodes(block)?;
+
+                // Table iteration: {~ for key, val in table }
+                if is_kv_form {
+                    let key_identifier = first;
+                    let val_identifier = args[1].try_as_identifier()?;
+                    let table_identifier = args[3].try_as_identifier()?;
+                    let resolved = context.resolve(&table_identifier);
+                    if resolved.is_none() && file.strict {
+                        return Err(Self::undefined_variable(&table_identifier));
+                    }
+
+                    if let Some(ContextValue::Table(table)) = resolved
+                        && !table.is_empty()
+                    {
+                        for (i, (k, v)) in table.iter().enumerate() {
+                            let mut loop_context = Self::loop_scope(context, i, table.len());
+                            loop_context
+                                .insert(key_identifier.clone(), ContextValue::Text(k.clone()));
+                            loop_context.insert(val_identifier.clone(), v.clone());
+
+                            Self::render_nodes(env, file, &mut loop_context, nodes, output)?;
+                        }
+                    }
+                } else {
+                    let item_identifier = first;
+
+                    // Path query:
+                    //   {~ for item in assets "path" [clauses] }
+                    let (items, clauses) = if is_assets_query {
+                        let dir_path = args[3].try_as_string()?;
+                        (query::collect_assets(context, &dir_path), &args[4..])
+
+                    // List iteration:
+                    //   {~ for item in collection [clauses] }
+                    } else {
+                        let collection_identifier = args
+                            .get(2)
+                            .ok_or(ProcessingError::Compilation {
+                                message: "missing collection identifier in for loop".into(),
+                            })?
+                            .try_as_identifier()?;
+                        let items = match context.resolve(&collection_identifier) {
+                            Some(ContextValue::List(items)) => items.clone(),
+                            None if file.strict => {
+                                return Err(Self::undefined_variable(&collection_identifier));
+                            }
+                            _ => vec![],
+                        };
+                        (items, &args[3..])
+                    };
+
+                    let items = LoopQuery::parse(clauses)?.apply(env, context, items)?;
+
+                    for (i, item) in items.iter().enumerate() {
+                        let mut loop_context = Self::loop_scope(context, i, items.len());
+                        loop_context.insert(item_identifier.clone(), item.clone());
+
+                        Self::render_nodes(env, file, &mut loop_context, nodes, output)?;
+                    }
                 }
             }
+
+            // Macro call: {~ namespace.name args }
+            _ if name.contains('.') => {
+                Self::call_macro(env, file, context, expression, output)?;
+            }
+
+            // Unknown template function.
+            _ => {
+                let message = format!("unknown template function: {}", name);
+                return Err(ProcessingError::Compilation {
+                    message: message.into(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Creates the scope for the iteration at `index` of a loop over
+    /// `length` items, containing a `loop` table of metadata about
+    /// the iteration. The enclosing loop's metadata (if any) is
+    /// available as `loop.parent`.
+    fn loop_scope(context: &LayeredContext, index: usize, length: usize) -> LayeredContext {
+        let mut metadata = Context::new();
+        let mut insert = |key: &str, value: ContextValue| {
+            metadata.insert(key.into(), value);
+        };
+        insert("index", ContextValue::Integer(index as i64 + 1));
+        insert("index0", ContextValue::Integer(index as i64));
+        insert("first", ContextValue::Bool(index == 0));
+        insert("last", ContextValue::Bool(index + 1 == length));
+        insert("length", ContextValue::Integer(length as i64));
+
+        let loop_key: Text = LOOP_CONTEXT_KEY.into();
+        if let Some(parent @ ContextValue::Table(_)) = context.get(&loop_key) {
+            metadata.insert("parent".into(), parent.clone());
         }
 
-        // There's sometimes a remainder from the lexer, which we can
-        // append directly to the compiled text.
-        output.push_str(lexer.remainder());
+        let mut loop_context = context.child_scope();
+        loop_context.insert(loop_key, ContextValue::Table(metadata));
+        loop_context
+    }
 
+    /// Returns an error if `name` can't be assigned by `set` or `capture`.
+    fn check_variable_name(name: &str) -> Result<(), ProcessingError> {
+        if name.contains('.') {
+            return Err(ProcessingError::Compilation {
+                message: format!("can't assign to dotted identifier: {}", name).into(),
+            });
+        }
         Ok(())
     }
 
-    /// Traverses a template block (e.g., an if block or for loop)
-    /// starting at the current position of `lexer`, returning
-    /// the span of the block (excluding the opening and closing
-    /// template expressions).
-    fn traverse_template_block(lexer: &mut Lexer<Token>) -> Result<Span, ProcessingError> {
-        // The end of the outermost template block is the end of the template itself.
-        if lexer.span().start == 0 {
-            return Ok(0..lexer.source().len());
+    /// Returns the error for a variable which couldn't be resolved in strict mode.
+    fn undefined_variable(identifier: &str) -> ProcessingError {
+        ProcessingError::Compilation {
+            message: format!("undefined variable: {}", identifier).into(),
         }
+    }
 
-        // The "start" of traversal is the end of the _current_
-        // span, since the immediate next token marks the beginning
-        // of the traversed block.
-        let start = lexer.span().end;
-        let mut end = lexer.span().end;
-
-        while let Some(token) = lexer.next() {
-            if let Ok(Token::OpenTemplate(Ok(TemplateExpression::Function { name, .. }))) = token {
-                match name.as_str() {
-                    // Nested block: traverse it fully.
-                    "if" | "for" => {
-                        let _ = Self::traverse_template_block(lexer)?;
-                    }
+    /// Splits any filters applied to the last of `args` from the arguments,
+    /// returning the unwrapped arguments and the filters.
+    fn split_filters(args: &[TemplateExpression]) -> (Vec<TemplateExpression>, &[Filter]) {
+        match args.split_last() {
+            Some((TemplateExpression::Filtered { value, filters }, rest)) => {
+                let mut args = rest.to_vec();
+                args.push((**value).clone());
+                (args, filters)
+            }
+            _ => (args.to_vec(), &[]),
+        }
+    }
 
-                    // End of the current block.
-                    "end" => {
-                        return Ok(start..end);
+    /// Evaluates a literal, identifier, or filtered value expression,
+    /// returning `None` if the value isn't present in the context.
+    fn evaluate_value(
+        env: &Environment,
+        context: &LayeredContext,
+        expression: &TemplateExpression,
+    ) -> Result<Option<ContextValue>, ProcessingError> {
+        match expression {
+            TemplateExpression::String(s) | TemplateExpression::Number(s) => {
+                Ok(Some(ContextValue::Text(s.clone())))
+            }
+            TemplateExpression::Identifier(id) => Ok(context.resolve(id).cloned()),
+            TemplateExpression::Filtered { value, filters } => {
+                let value = Self::evaluate_value(env, context, value)?;
+                Self::apply_filters(env, value, filters)
+            }
+            expression => Err(ProcessingError::Compilation {
+                message: format!("expected value; got {:?}", expression).into(),
+            }),
+        }
+    }
+
+    /// Renders a context value as text, returning `None` if the
+    /// value can't be rendered (e.g., an unresolved asset reference).
+    fn render_value(env: &Environment, value: &ContextValue) -> Option<Text> {
+        match value {
+            ContextValue::AssetRef(path) => env
+                .asset_outputs
+                .get(path.as_str())
+                .map(|output_path| format!("/{}", output_path).into()),
+            ContextValue::List(items) => {
+                let mut s = String::from("[");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        s.push_str(", ");
+                    }
+                    match item.to_text() {
+                        Some(text) => s.push_str(&text),
+                        None => {
+                            s.push_str(&format!("{:?}", item));
+                        }
                     }
-                    _ => {}
                 }
+                s.push(']');
+                Some(s.into())
             }
-
-            end = lexer.span().end;
+            ContextValue::Table(_) => None,
+            scalar => scalar.to_text(),
         }
+    }
 
-        Err(ProcessingError::Compilation {
-            message: format!(
-                "template contained an unclosed block: {}",
-                &lexer.source()[start..]
+    /// Returns true if `lhs` equals `rhs`: numerically or chronologically
+    /// if either is a number or date, and by their rendered text otherwise.
+    fn values_equal(env: &Environment, lhs: &ContextValue, rhs: &ContextValue) -> bool {
+        let typed = |value: &ContextValue| {
+            matches!(
+                value,
+                ContextValue::Integer(_) | ContextValue::Number(_) | ContextValue::Date(_)
             )
-            .into(),
-        })
+        };
+        if typed(lhs) || typed(rhs) {
+            // Integers are compared exactly, as they may not fit in a float.
+            if let (Some(lhs), Some(rhs)) = (Self::value_integer(lhs), Self::value_integer(rhs)) {
+                return lhs == rhs;
+            }
+            if let (Some(lhs), Some(rhs)) = (Self::value_number(lhs), Self::value_number(rhs)) {
+                return lhs == rhs;
+            }
+            if let (Some(lhs), Some(rhs)) = (Self::value_date(lhs), Self::value_date(rhs)) {
+                return lhs == rhs;
+            }
+        }
+
+        let lhs = Self::render_value(env, lhs);
+        lhs.is_some() && lhs == Self::render_value(env, rhs)
     }
 
-    /// Extracts a string value from a context value for sort comparison.
-    fn extract_sort_value(value: &ContextValue, key: &str) -> Option<String> {
+    /// Returns the integer `value` represents, if it's an integer or integer text.
+    fn value_integer(value: &ContextValue) -> Option<i64> {
         match value {
-            ContextValue::Table(table) => {
-                let k: Text = key.into();
-                match table.get(&k) {
-                    Some(ContextValue::Text(t)) => Some(t.to_string()),
-                    _ => None,
-                }
+            ContextValue::Integer(integer) => Some(*integer),
+            ContextValue::Text(text) => text.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the number `value` represents, if it's a number or numeric text.
+    fn value_number(value: &ContextValue) -> Option<f64> {
+        match value {
+            ContextValue::Integer(integer) => Some(*integer as f64),
+            ContextValue::Number(number) => Some(*number),
+            ContextValue::Text(text) => text.trim().parse().ok().filter(|n: &f64| n.is_finite()),
+            _ => None,
+        }
+    }
+
+    /// Returns the date and time `value` represents, if it's a date or text
+    /// formatted as a date (see [Self::parse_date]). Dates without a time
+    /// are at midnight, and times are as written, ignoring any offset.
+    fn value_date(value: &ContextValue) -> Option<NaiveDateTime> {
+        match value {
+            ContextValue::Date(datetime) => {
+                let date = datetime.date?;
+                let date =
+                    NaiveDate::from_ymd_opt(date.year.into(), date.month.into(), date.day.into())?;
+                let time = match datetime.time {
+                    Some(time) => NaiveTime::from_hms_nano_opt(
+                        time.hour.into(),
+                        time.minute.into(),
+                        time.second.unwrap_or(0).into(),
+                        time.nanosecond.unwrap_or(0),
+                    )?,
+                    None => NaiveTime::MIN,
+                };
+                Some(date.and_time(time))
             }
+            ContextValue::Text(text) => Some(Self::parse_date(text)?.and_time(NaiveTime::MIN)),
             _ => None,
         }
     }
 
+    /// Passes `value` through each of `filters` in order.
+    ///
+    /// Missing values pass through filters untouched,
+    /// except for `default`, which replaces them.
+    fn apply_filters(
+        env: &Environment,
+        mut value: Option<ContextValue>,
+        filters: &[Filter],
+    ) -> Result<Option<ContextValue>, ProcessingError> {
+        for filter in filters {
+            let arg = |index: usize| -> Result<&Text, ProcessingError> {
+                match filter.args.get(index) {
+                    Some(TemplateExpression::String(s) | TemplateExpression::Number(s)) => Ok(s),
+                    _ => Err(ProcessingError::Compilation {
+                        message: format!(
+                            "missing argument {} for '{}' filter",
+                            index + 1,
+                            filter.name
+                        )
+                        .into(),
+                    }),
+                }
+            };
+
+            // Filters that operate on the value (or its absence) directly.
+            match filter.name.as_str() {
+                "default" => {
+                    let is_empty = match &value {
+                        None => true,
+                        Some(ContextValue::Text(text)) => text.is_empty(),
+                        Some(ContextValue::List(items)) => items.is_empty(),
+                        Some(_) => false,
+                    };
+                    if is_empty {
+                        value = Some(ContextValue::Text(arg(0)?.clone()));
+                    }
+                    continue;
+                }
+                "join" => {
+                    if let Some(ContextValue::List(items)) = &value {
+                        let separator = arg(0)?;
+                        let joined = items
+                            .iter()
+                            .filter_map(|item| Self::render_value(env, item))
+                            .map(|item| item.to_string())
+                            .collect::<Vec<_>>()
+                            .join(separator.as_str());
+                        value = Some(ContextValue::Text(joined.into()));
+                    }
+                    continue;
+                }
+                "length" => {
+                    let length = match &value {
+                        Some(ContextValue::List(items)) => items.len(),
+                        Some(ContextValue::Table(table)) => table.len(),
+                        Some(value) => {
+                            Self::render_value(env, value).map_or(0, |text| text.chars().count())
+                        }
+                        None => 0,
+                    };
+                    value = Some(ContextValue::Integer(length as i64));
+                    continue;
+                }
+                // Marks the value as trusted, excluding it from escaping.
+                "raw" => continue,
+                _ => {}
+            }
+
+            // All other filters operate on the value's text.
+            let Some(text) = value.as_ref().and_then(|v| Self::render_value(env, v)) else {
+                continue;
+            };
+            let filtered: String = match filter.name.as_str() {
+                "upper" => text.to_uppercase(),
+                "lower" => text.to_lowercase(),
+                "trim" => text.trim().to_string(),
+                "slugify" => slugify(&text),
+                "escape" => Self::escape_html(&text),
+                "urlencode" => Self::urlencode(&text),
+                "truncate" => {
+                    let length = arg(0)?;
+                    let length: usize =
+                        length.parse().map_err(|_| ProcessingError::Compilation {
+                            message: format!("invalid length for 'truncate' filter: {}", length)
+                                .into(),
+                        })?;
+                    if text.chars().count() > length {
+                        let truncated: String = text.chars().take(length).collect();
+                        format!("{}…", truncated.trim_end())
+                    } else {
+                        text.to_string()
+                    }
+                }
+                "replace" => text.replace(arg(0)?.as_str(), arg(1)?.as_str()),
+                name => {
+                    return Err(ProcessingError::Compilation {
+                        message: format!("unknown template filter: {}", name).into(),
+                    });
+                }
+            };
+            value = Some(ContextValue::Text(filtered.into()));
+        }
+
+        Ok(value)
+    }
+
+    /// Returns true if `filters` mark a value as trusted (`raw`)
+    /// or already escaped (`escape`), so it shouldn't be auto-escaped.
+    fn is_trusted(filters: &[Filter]) -> bool {
+        filters
+            .iter()
+            .any(|filter| filter.name == "raw" || filter.name == "escape")
+    }
+
+    /// Appends `text` to `output`, escaping it if required by `file`.
+    ///
+    /// Text output inside an HTML tag (i.e., in an attribute) has both
+    /// markup characters and quotes escaped; text output anywhere else
+    /// only has markup characters escaped.
+    fn push_escaped(file: &TemplateFile, output: &mut String, text: &str) {
+        if !file.escape_html {
+            output.push_str(text);
+            return;
+        }
+
+        if Self::ends_in_tag(file, output) {
+            output.push_str(&Self::escape_html(text));
+        } else {
+            for char in text.chars() {
+                match char {
+                    '&' => output.push_str("&amp;"),
+                    '<' => output.push_str("&lt;"),
+                    '>' => output.push_str("&gt;"),
+                    char => output.push(char),
+                }
+            }
+        }
+    }
+
+    /// Returns true if `output`, rendered from `file`, ends inside an HTML
+    /// tag. If `output` contains no markup, it continues the output
+    /// enclosing it, and ends inside a tag if [TemplateFile::in_tag] is set.
+    fn ends_in_tag(file: &TemplateFile, output: &str) -> bool {
+        match output.rfind(['<', '>']) {
+            Some(i) => {
+                output[i..].starts_with('<')
+                    && output[i + 1..]
+                        .chars()
+                        .next()
+                        .is_some_and(|c| c.is_ascii_alphabetic())
+            }
+            None => file.in_tag,
+        }
+    }
+
+    /// Escapes the HTML special characters in `text`.
+    fn escape_html(text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+        for char in text.chars() {
+            match char {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                char => escaped.push(char),
+            }
+        }
+        escaped
+    }
+
+    /// Percent-encodes all characters in `text` except
+    /// for unreserved URL characters (`A-Z a-z 0-9 - _ . ~`).
+    fn urlencode(text: &str) -> String {
+        let mut encoded = String::with_capacity(text.len());
+        for byte in text.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    encoded.push(byte as char)
+                }
+                byte => encoded.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+        encoded
+    }
+
+    /// Returns the content of the part at `path`, and the
+    /// number of lines of frontmatter preceding it.
+    fn part<'c>(
+        context: &'c LayeredContext,
+        path: &str,
+    ) -> Result<(&'c Text, usize), ProcessingError> {
+        let part_key: Text = format!("{}{}", PART_CONTEXT_PREFIX, path).into();
+        let Some(ContextValue::Text(content)) = context.get(&part_key) else {
+            return Err(ProcessingError::Compilation {
+                message: format!("part not found: {}", path).into(),
+            });
+        };
+
+        let lines_key: Text = format!("{}{}", PART_LINES_PREFIX, path).into();
+        let line_offset = match context.get(&lines_key) {
+            Some(ContextValue::Text(lines)) => lines.parse().unwrap_or(0),
+            _ => 0,
+        };
+
+        Ok((content, line_offset))
+    }
+
+    /// Returns the template parsed from `source`, which
+    /// is parsed once per [Environment::templates] cache.
+    fn parse(env: &Environment, source: &str, trim_blocks: bool) -> Arc<Template> {
+        env.templates.get_or_parse(source, trim_blocks)
+    }
+
+    /// Returns the nodes of `block`, which may not contain `else` clauses.
+    fn block_nodes(block: &Option<Vec<Branch>>) -> Result<&[Node], ProcessingError> {
+        match block.as_deref() {
+            Some([branch]) => Ok(&branch.nodes),
+            _ => Err(ProcessingError::Compilation {
+                message: "unexpected 'else' outside of an if block".into(),
+            }),
+        }
+    }
+
     /// Parses a `YYYY-MM-DD` date, with or without a trailing time component.
     fn parse_date(s: &str) -> Option<NaiveDate> {
         let date_part = s.trim().split('T').next()?;
@@ -706,17 +1435,40 @@ mod tests {
         if let Some(defaults) = defaults {
             let ctx_key: Text = format!("{}{}", PART_DEFAULTS_PREFIX, path).into();
             ctx.insert(ctx_key, ContextValue::Table(defaults));
+            let lines_key: Text = format!("{}{}", PART_LINES_PREFIX, path).into();
+            let lines = text[..text.len() - body.len()].matches('\n').count();
+            ctx.insert(lines_key, ContextValue::Text(lines.to_string().into()));
+        }
+    }
+
+    /// Runs the template processor, returning the diagnostic it fails with.
+    fn diagnose(processor: &TemplateProcessor, ctx: &Context, content: &str) -> TemplateDiagnostic {
+        let mut asset = Asset::new("page.html".into(), content.as_bytes().to_vec());
+        let lctx = LayeredContext::from_flat(ctx.clone());
+        match processor.process(&Environment::test(), &lctx, &mut asset) {
+            Err(ProcessingError::Template { diagnostic }) => *diagnostic,
+            result => panic!("expected a template diagnostic, got {:?}", result),
         }
     }
 
     /// Wraps a flat context and runs the template processor.
     fn run(ctx: &Context, asset: &mut Asset) {
         let lctx = LayeredContext::from_flat(ctx.clone());
-        TemplateProcessor
+        TemplateProcessor::default()
             .process(&Environment::test(), &lctx, asset)
             .unwrap();
     }
 
+    /// Runs `processor` on an HTML asset containing `content`, returning the output.
+    fn render(processor: &TemplateProcessor, ctx: &Context, content: &str) -> String {
+        let mut asset = Asset::new("page.html".into(), content.as_bytes().to_vec());
+        let lctx = LayeredContext::from_flat(ctx.clone());
+        processor
+            .process(&Environment::test(), &lctx, &mut asset)
+            .unwrap();
+        asset.as_text().unwrap().to_string()
+    }
+
     fn get_text(ctx: &Context, key: &str) -> Option<Text> {
         let key: Text = key.into();
         match ctx.get(&key) {
@@ -824,14 +1576,106 @@ mod tests {
     }
 
     #[test]
-    fn extracts_frontmatter() {
-        let content = r#"title = "Hello"
-author = "Test"
-
-***
+    fn for_loop_exposes_loop_metadata() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ for item in items}{~ if loop.first}<{~ end}{~ get loop.index}/{~ get loop.length}:{~ get item}({~ get loop.index0}){~ if not loop.last}, {~ else}>{~ end}{~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
 
-<h1>{~ get title}</h1>"#;
-        let mut asset = Asset::new("page.html".into(), content.as_bytes().to_vec());
+        let ctx: Context = [(
+            "items".into(),
+            ContextValue::List(vec![
+                ContextValue::Text("a".into()),
+                ContextValue::Text("b".into()),
+                ContextValue::Text("c".into()),
+            ]),
+        )]
+        .into();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "<1/3:a(0), 2/3:b(1), 3/3:c(2)>");
+
+        // Loop metadata is numeric, so it compares as numbers.
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ for item in items}{~ if loop.index > 9}{~ get item}{~ end}{~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        let items = (0..11).map(|i| ContextValue::Text(i.to_string().into()));
+        let ctx: Context = [("items".into(), ContextValue::List(items.collect()))].into();
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "910");
+    }
+
+    #[test]
+    fn nested_loops_expose_parent_metadata() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ for row in rows}{~ for col in cols}{~ get loop.parent.index}.{~ get loop.index} {~ end}{~ end}{~ get loop.index}"#
+                .as_bytes()
+                .to_vec(),
+        );
+
+        let list = ContextValue::List(vec![
+            ContextValue::Text("x".into()),
+            ContextValue::Text("y".into()),
+        ]);
+        let ctx: Context = [("rows".into(), list.clone()), ("cols".into(), list)].into();
+        run(&ctx, &mut asset);
+
+        // Loop metadata doesn't leak out of the loop.
+        assert_eq!(
+            asset.as_text().unwrap(),
+            "1.1 1.2 2.1 2.2 {~ get loop.index }"
+        );
+    }
+
+    #[test]
+    fn for_kv_and_assets_loops_expose_loop_metadata() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ for k, v in colors}{~ get loop.index}={~ get k}{~ if not loop.last},{~ end}{~ end};{~ for post in assets "blog"}{~ if loop.last}{~ get post.title}/{~ get loop.length}{~ end}{~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+
+        let mut colors = Context::default();
+        colors.insert("blue".into(), ContextValue::Text("#00f".into()));
+        colors.insert("red".into(), ContextValue::Text("#f00".into()));
+
+        let mut entry1 = Context::default();
+        entry1.insert("title".into(), ContextValue::Text("hello".into()));
+        let mut entry2 = Context::default();
+        entry2.insert("title".into(), ContextValue::Text("world".into()));
+
+        let ctx: Context = [
+            ("colors".into(), ContextValue::Table(colors)),
+            (
+                "_assets:blog".into(),
+                ContextValue::List(vec![
+                    ContextValue::Table(entry1),
+                    ContextValue::Table(entry2),
+                ]),
+            ),
+        ]
+        .into();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "1=blue,2=red;world/2");
+    }
+
+    #[test]
+    fn extracts_frontmatter() {
+        let content = r#"title = "Hello"
+author = "Test"
+
+***
+
+<h1>{~ get title}</h1>"#;
+        let mut asset = Asset::new("page.html".into(), content.as_bytes().to_vec());
         let mut ctx = Context::default();
         apply_frontmatter(&mut ctx, &mut asset);
         run(&ctx, &mut asset);
@@ -876,21 +1720,62 @@ Body"#;
     fn handles_various_types() {
         let content = r#"name = "test"
 count = 42
+big = 9007199254740993
 ratio = 3.14
 enabled = true
+date = 2025-04-17
+time = 07:30:00
 
 ***
 
-Body"#;
+{~ get count} {~ get big} {~ get ratio} {~ get enabled} {~ get date} {~ get time}{~ if big is "9007199254740992"} lossy{~ end}"#;
         let mut asset = Asset::new("page.html".into(), content.as_bytes().to_vec());
         let mut ctx = Context::default();
         apply_frontmatter(&mut ctx, &mut asset);
-        run(&ctx, &mut asset);
 
         assert_eq!(get_text(&ctx, "name"), Some("test".into()));
-        assert_eq!(get_text(&ctx, "count"), Some("42".into()));
-        assert_eq!(get_text(&ctx, "ratio"), Some("3.14".into()));
-        assert_eq!(get_text(&ctx, "enabled"), Some("true".into()));
+        assert!(matches!(
+            ctx.get(&"count".into()),
+            Some(ContextValue::Integer(42))
+        ));
+        assert!(matches!(
+            ctx.get(&"ratio".into()),
+            Some(ContextValue::Number(_))
+        ));
+        assert!(matches!(
+            ctx.get(&"enabled".into()),
+            Some(ContextValue::Bool(true))
+        ));
+        assert!(matches!(
+            ctx.get(&"date".into()),
+            Some(ContextValue::Date(_))
+        ));
+        assert_eq!(get_text(&ctx, "time"), Some("07:30:00".into()));
+
+        // Typed values render as they're written.
+        run(&ctx, &mut asset);
+        assert_eq!(
+            asset.as_text().unwrap().trim(),
+            "42 9007199254740993 3.14 true 2025-04-17 07:30:00"
+        );
+    }
+
+    #[test]
+    fn compares_typed_values() {
+        let content = r#"weight = 10
+light = 9.5
+draft = false
+zero = 0
+date = 2025-04-17T10:30:00Z
+
+***
+{~ if weight > light}a{~ end}{~ if weight is 10.0}b{~ end}{~ if draft or zero}c{~ end}{~ if date > "2025-04-17"}d{~ end}{~ date date "%Y-%m-%d %H:%M"}"#;
+        let mut asset = Asset::new("page.html".into(), content.as_bytes().to_vec());
+        let mut ctx = Context::default();
+        apply_frontmatter(&mut ctx, &mut asset);
+
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap().trim(), "abd2025-04-17 10:30");
     }
 
     #[test]
@@ -1031,106 +1916,1130 @@ a delimiter in it"#;
         );
         let ctx: Context = [("data".into(), ContextValue::Table(nested))].into();
         run(&ctx, &mut asset);
-
-        assert_eq!(asset.as_text().unwrap(), "a b c ");
+
+        assert_eq!(asset.as_text().unwrap(), "a b c ");
+    }
+
+    #[test]
+    fn deeply_nested_dotted_access() {
+        let mut asset = Asset::new("test.html".into(), r#"{~ get a.b.c}"#.as_bytes().to_vec());
+
+        let mut inner = Context::default();
+        inner.insert("c".into(), ContextValue::Text("deep".into()));
+        let mut outer = Context::default();
+        outer.insert("b".into(), ContextValue::Table(inner));
+        let ctx: Context = [("a".into(), ContextValue::Table(outer))].into();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "deep");
+    }
+
+    #[test]
+    fn missing_dotted_path() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ get user.missing}"#.as_bytes().to_vec(),
+        );
+
+        let ctx = Context::default();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "{~ get user.missing }");
+    }
+
+    #[test]
+    fn get_fallback_uses_first_resolved() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ get title or name}"#.as_bytes().to_vec(),
+        );
+
+        let ctx: Context = [("name".into(), ContextValue::Text("Alice".into()))].into();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "Alice");
+    }
+
+    #[test]
+    fn get_fallback_prefers_first() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ get title or name}"#.as_bytes().to_vec(),
+        );
+
+        let ctx: Context = [
+            ("title".into(), ContextValue::Text("Hello".into())),
+            ("name".into(), ContextValue::Text("Alice".into())),
+        ]
+        .into();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "Hello");
+    }
+
+    #[test]
+    fn get_fallback_chain() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ get a or b or c}"#.as_bytes().to_vec(),
+        );
+
+        let ctx: Context = [("c".into(), ContextValue::Text("third".into()))].into();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "third");
+    }
+
+    #[test]
+    fn get_fallback_none_resolved() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ get title or name}"#.as_bytes().to_vec(),
+        );
+
+        let ctx = Context::default();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "{~ get title or name }");
+    }
+
+    #[test]
+    fn get_fallback_with_dotted() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ get page.title or site.name}"#.as_bytes().to_vec(),
+        );
+
+        let mut site = Context::default();
+        site.insert("name".into(), ContextValue::Text("My Site".into()));
+        let ctx: Context = [("site".into(), ContextValue::Table(site))].into();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "My Site");
+    }
+
+    #[test]
+    fn get_applies_filters() {
+        let cases = [
+            (r#"{~ get title | upper}"#, "HELLO, WORLD!"),
+            (r#"{~ get title | lower}"#, "hello, world!"),
+            (r#"{~ get title | slugify}"#, "hello-world"),
+            (r#"{~ get title | truncate 5}"#, "Hello…"),
+            (r#"{~ get title | truncate 60}"#, "Hello, World!"),
+            (r#"{~ get title | replace "World" "Aer"}"#, "Hello, Aer!"),
+            (r#"{~ get title | urlencode}"#, "Hello%2C%20World%21"),
+            (r#"{~ get padded | trim}"#, "padded"),
+            (
+                r#"{~ get markup | escape}"#,
+                "&lt;b&gt;&quot;Tom&quot; &amp; Jerry&lt;/b&gt;",
+            ),
+            (r#"{~ get tags | join ", "}"#, "rust, web"),
+            (r#"{~ get tags | length}"#, "2"),
+            (r#"{~ get title | length}"#, "13"),
+            (
+                r#"{~ get title | replace "," "" | slugify | upper}"#,
+                "HELLO-WORLD",
+            ),
+        ];
+
+        for (template, expected) in cases {
+            let mut asset = Asset::new("test.html".into(), template.as_bytes().to_vec());
+            let ctx: Context = [
+                ("title".into(), ContextValue::Text("Hello, World!".into())),
+                ("padded".into(), ContextValue::Text("  padded \n".into())),
+                (
+                    "markup".into(),
+                    ContextValue::Text(r#"<b>"Tom" & Jerry</b>"#.into()),
+                ),
+                (
+                    "tags".into(),
+                    ContextValue::List(vec![
+                        ContextValue::Text("rust".into()),
+                        ContextValue::Text("web".into()),
+                    ]),
+                ),
+            ]
+            .into();
+            run(&ctx, &mut asset);
+
+            assert_eq!(asset.as_text().unwrap(), expected, "{}", template);
+        }
+    }
+
+    #[test]
+    fn get_default_filter_replaces_missing_values() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ get title or name | default "Untitled"}|{~ get empty | default "x"}"#
+                .as_bytes()
+                .to_vec(),
+        );
+
+        let ctx: Context = [("empty".into(), ContextValue::Text("".into()))].into();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "Untitled|x");
+    }
+
+    #[test]
+    fn get_filters_apply_to_fallback_chain() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ get title or name | upper}"#.as_bytes().to_vec(),
+        );
+
+        let ctx: Context = [("name".into(), ContextValue::Text("Alice".into()))].into();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "ALICE");
+    }
+
+    #[test]
+    fn unknown_filter_is_an_error() {
+        let content = r#"{~ get title | shout}"#;
+        let mut asset = Asset::new("page.html".into(), content.as_bytes().to_vec());
+        let ctx: Context = [("title".into(), ContextValue::Text("Hi".into()))].into();
+
+        let lctx = LayeredContext::from_flat(ctx);
+        let result = TemplateProcessor::default().process(&Environment::test(), &lctx, &mut asset);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn diagnostics_locate_errors() {
+        let ctx: Context = [(
+            "xs".into(),
+            ContextValue::List(vec![ContextValue::Text("x".into())]),
+        )]
+        .into();
+
+        // Errors are located within nested blocks.
+        let diagnostic = diagnose(
+            &TemplateProcessor::default(),
+            &ctx,
+            "<ul>\n{~ for x in xs }\n  {~ if x }<li>{~ get x | shout }</li>{~ end }\n{~ end }",
+        );
+        assert_eq!("unknown template filter: shout", diagnostic.message);
+        assert_eq!(
+            TemplateLocation {
+                path: "page.html".into(),
+                line: 3,
+                column: 16,
+            },
+            diagnostic.location
+        );
+        assert_eq!(
+            "  {~ if x }<li>{~ get x | shout }</li>{~ end }",
+            diagnostic.source_line
+        );
+        assert_eq!("{~ get x | shout }".len(), diagnostic.width);
+
+        // Unclosed blocks are located at their opening expression.
+        let diagnostic = diagnose(
+            &TemplateProcessor::default(),
+            &ctx,
+            "<p>\n  {~ if a }{~ for x in xs }{~ end }\n</p>",
+        );
+        assert_eq!(
+            "unclosed block; expected a matching '{~ end}'",
+            diagnostic.message
+        );
+        assert_eq!(
+            (2, 3),
+            (diagnostic.location.line, diagnostic.location.column)
+        );
+    }
+
+    #[test]
+    fn diagnostics_track_included_parts() {
+        let mut ctx = Context::new();
+        register_part(
+            &mut ctx,
+            "_inner.html",
+            "title = \"Inner\"\n***\n<p>{~ bogus }</p>",
+        );
+        register_part(
+            &mut ctx,
+            "_outer.html",
+            "<div>\n{~ use \"_inner.html\" }</div>",
+        );
+
+        let diagnostic = diagnose(
+            &TemplateProcessor::default(),
+            &ctx,
+            "\n\n  {~ use \"_outer.html\" }",
+        );
+        assert_eq!("unknown template function: bogus", diagnostic.message);
+        assert_eq!(
+            TemplateLocation {
+                path: "_inner.html".into(),
+                line: 3,
+                column: 4,
+            },
+            diagnostic.location
+        );
+        assert_eq!(
+            vec![
+                TemplateLocation {
+                    path: "_outer.html".into(),
+                    line: 2,
+                    column: 1,
+                },
+                TemplateLocation {
+                    path: "page.html".into(),
+                    line: 3,
+                    column: 3,
+                },
+            ],
+            diagnostic.include_stack
+        );
+    }
+
+    #[test]
+    fn strict_mode_rejects_undefined_variables() {
+        let mut ctx: Context = [
+            ("title".into(), ContextValue::Text("Hi".into())),
+            ("date".into(), ContextValue::Text("2025-01-02".into())),
+        ]
+        .into();
+        register_part(&mut ctx, "_card.html", "{~ get heading }");
+        let strict = TemplateProcessor::new(true, false);
+
+        for (template, missing) in [
+            ("{~ get nope }", "nope"),
+            ("{~ get nope or also_nope }", "nope or also_nope"),
+            ("{~ date nope \"%Y\" }", "nope"),
+            ("{~ for x in nope }{~ end }", "nope"),
+            ("{~ for k, v in nope }{~ end }", "nope"),
+            (
+                "{~ use \"_card.html\", with nope | upper as heading }",
+                "nope",
+            ),
+        ] {
+            assert_eq!(
+                format!("undefined variable: {}", missing),
+                diagnose(&strict, &ctx, template).message,
+                "{}",
+                template
+            );
+        }
+
+        // Resolved and defaulted variables still compile.
+        let mut asset = Asset::new(
+            "page.html".into(),
+            "{~ get nope | default \"x\" } {~ get title } {~ date date \"%Y\" }"
+                .as_bytes()
+                .to_vec(),
+        );
+        let lctx = LayeredContext::from_flat(ctx);
+        strict
+            .process(&Environment::test(), &lctx, &mut asset)
+            .unwrap();
+        assert_eq!("x Hi 2025", asset.as_text().unwrap());
+    }
+
+    #[test]
+    fn trim_markers_remove_whitespace() {
+        let ctx: Context = [(
+            "xs".into(),
+            ContextValue::List(vec![
+                ContextValue::Text("a".into()),
+                ContextValue::Text("b".into()),
+            ]),
+        )]
+        .into();
+        let processor = TemplateProcessor::default();
+
+        // Trimming around and inside blocks.
+        assert_eq!(
+            "<ul><li>a</li><li>b</li>\n</ul>",
+            render(
+                &processor,
+                &ctx,
+                "<ul>\n  {~- for x in xs -}\n    <li>{~ get x }</li>\n  {~- end }\n</ul>"
+            )
+        );
+        assert_eq!(
+            "B",
+            render(&processor, &ctx, "{~ if nope -} A {~- else -} B {~- end }")
+        );
+
+        // Trimming around expressions.
+        assert_eq!(
+            "Hello xx",
+            render(
+                &processor,
+                &ctx,
+                "{~ set a = \"x\" -}\n\nHello {~ get a } \n {~- raw a }"
+            )
+        );
+
+        // Whitespace inside values is never trimmed.
+        assert_eq!(
+            " x ",
+            render(&processor, &ctx, "{~ set a = \" x \" -} {~- get a }")
+        );
+    }
+
+    #[test]
+    fn trim_blocks_removes_newlines_after_blocks() {
+        let ctx: Context = [(
+            "xs".into(),
+            ContextValue::List(vec![
+                ContextValue::Text("a".into()),
+                ContextValue::Text("b".into()),
+            ]),
+        )]
+        .into();
+        let template = "{~ for x in xs }\n{~ if x is \"a\" }\nA\n{~ else }\n{~ get x }\n{~ end }\n{~ end }\ndone\n";
+
+        assert_eq!(
+            "A\nb\ndone\n",
+            render(&TemplateProcessor::new(false, true), &ctx, template)
+        );
+        assert_eq!(
+            "\n\nA\n\n\n\nb\n\n\ndone\n",
+            render(&TemplateProcessor::default(), &ctx, template)
+        );
+    }
+
+    #[test]
+    fn comments_render_nothing() {
+        let ctx = Context::new();
+        let processor = TemplateProcessor::default();
+        assert_eq!(
+            "<p>Hi</p>",
+            render(
+                &processor,
+                &ctx,
+                "<p>{~# a \"note\" {~ get x }\nacross lines }Hi</p>"
+            )
+        );
+        assert_eq!("AB", render(&processor, &ctx, "A{~# .a { color: red } }B"));
+
+        let diagnostic = diagnose(&processor, &ctx, "<p>\n{~# unclosed</p>");
+        assert_eq!("unclosed comment", diagnostic.message);
+        assert_eq!(
+            (2, 1),
+            (diagnostic.location.line, diagnostic.location.column)
+        );
+    }
+
+    #[test]
+    fn verbatim_copies_block_content() {
+        let ctx: Context = [("yes".into(), ContextValue::Text("true".into()))].into();
+        let processor = TemplateProcessor::default();
+
+        assert_eq!(
+            "<code>{~ get x } {~ if a }<b>{~ else }{~ end } {~# c }</code>",
+            render(
+                &processor,
+                &ctx,
+                "<code>{~ verbatim }{~ get x } {~ if a }<b>{~ else }{~ end } {~# c }{~ end }</code>"
+            )
+        );
+        assert_eq!(
+            "{~ get x }",
+            render(
+                &processor,
+                &ctx,
+                "{~ if yes }{~ verbatim -} {~ get x } {~- end }{~ end }"
+            )
+        );
+
+        // Content closed by `end verbatim` is copied as-is,
+        // even if it contains unbalanced expressions.
+        assert_eq!(
+            "<p>Close blocks with {~ end}.</p>|Open one with {~ if x}.",
+            render(
+                &processor,
+                &ctx,
+                "<p>{~ verbatim}Close blocks with {~ end}.{~ end verbatim}</p>|{~ if yes}{~ verbatim -}\n Open one with {~ if x}.\n{~- end verbatim }{~ end}"
+            )
+        );
+
+        // A `verbatim` block without `end verbatim` isn't
+        // closed by the `end verbatim` of a following block.
+        assert_eq!(
+            "a{~ get x}|b{~ end}",
+            render(
+                &processor,
+                &ctx,
+                "{~ verbatim}a{~ get x}{~ end}|{~ verbatim}b{~ end}{~ end verbatim}"
+            )
+        );
+    }
+
+    #[test]
+    fn extends_overrides_blocks() {
+        let mut ctx = Context::new();
+        register_part(
+            &mut ctx,
+            "_base.html",
+            "<title>{~ block title }Site{~ end }</title>{~ block body }<p>Base</p>{~ end }",
+        );
+        register_part(
+            &mut ctx,
+            "_blog.html",
+            "{~ extends \"_base.html\" }{~ block body }<main>{~ block post }{~ end }</main>{~ end }",
+        );
+        let processor = TemplateProcessor::default();
+
+        // Blocks which aren't overridden keep the parent's content,
+        // and content outside of blocks is discarded.
+        assert_eq!(
+            "<title>About</title><p>Base</p>",
+            render(
+                &processor,
+                &ctx,
+                "{~ extends \"_base.html\" }ignored{~ block title }About{~ end }"
+            )
+        );
+
+        // Blocks may be overridden through several levels of templates.
+        assert_eq!(
+            "<title>Site</title><main><p>Post</p></main>",
+            render(
+                &processor,
+                &ctx,
+                "{~ extends \"_blog.html\" }{~ block post }<p>Post</p>{~ end }"
+            )
+        );
+
+        // Variables set outside of blocks are visible to parents.
+        assert_eq!(
+            "<title>Home</title><p>Base</p>",
+            render(
+                &processor,
+                &ctx,
+                "{~ extends \"_base.html\" }{~ set name = \"Home\" }{~ block title }{~ get name }{~ end }"
+            )
+        );
+    }
+
+    #[test]
+    fn super_renders_parent_blocks() {
+        let mut ctx = Context::new();
+        register_part(&mut ctx, "_base.html", "{~ block head }<meta>{~ end }");
+        register_part(&mut ctx, "_root.html", "{~ block head }{~ super }{~ end }");
+        register_part(
+            &mut ctx,
+            "kit/_page.html",
+            "{~ extends \"_base.html\" }{~ block head }{~ super }<link>{~ end }",
+        );
+        let processor = TemplateProcessor::default();
+
+        assert_eq!(
+            "<meta><link><script>",
+            render(
+                &processor,
+                &ctx,
+                "{~ extends \"kit/_page.html\" }{~ block head }{~ super }<script>{~ end }"
+            )
+        );
+
+        // `super` is only valid in blocks overriding another block.
+        assert_eq!(
+            "no parent definition of block 'head' for 'super'",
+            diagnose(&processor, &ctx, "{~ extends \"_root.html\" }").message
+        );
+        assert_eq!(
+            "unexpected 'super' outside of a block",
+            diagnose(&processor, &ctx, "{~ super }").message
+        );
+    }
+
+    #[test]
+    fn extends_reports_errors() {
+        let mut ctx = Context::new();
+        ctx.insert("yes".into(), ContextValue::Text("true".into()));
+        register_part(&mut ctx, "_a.html", "{~ extends \"_b.html\" }");
+        register_part(&mut ctx, "_b.html", "{~ extends \"_a.html\" }");
+        let processor = TemplateProcessor::default();
+
+        let diagnostic = diagnose(&processor, &ctx, "{~ extends \"_a.html\" }");
+        assert_eq!("circular extends of _a.html", diagnostic.message);
+        assert_eq!("_b.html", diagnostic.location.path);
+        assert_eq!(
+            "part not found: _missing.html",
+            diagnose(&processor, &ctx, "{~ extends \"_missing.html\" }").message
+        );
+        assert_eq!(
+            "unexpected 'extends' outside of the top level of a template",
+            diagnose(
+                &processor,
+                &ctx,
+                "{~ if yes }{~ extends \"_a.html\" }{~ end }"
+            )
+            .message
+        );
+    }
+
+    #[test]
+    fn calls_imported_macros() {
+        let mut ctx = Context::new();
+        ctx.insert("shop".into(), ContextValue::Text("/shop".into()));
+        register_part(
+            &mut ctx,
+            "kit/_macros.html",
+            "{~ import \"_icons.html\" as icons }\
+             {~ macro button(label, href, style = \"primary\") }\
+             <a class=\"{~ get style }\" href=\"{~ get href }\">{~ icons.star }{~ get label }</a>\
+             {~ end }",
+        );
+        register_part(&mut ctx, "_icons.html", "{~ macro star }*{~ end }");
+        let processor = TemplateProcessor::default();
+
+        assert_eq!(
+            "<a class=\"primary\" href=\"/shop\">*Buy</a> \
+             <a class=\"ghost\" href=\"/\">*Home &amp; more</a>",
+            render(
+                &processor,
+                &ctx,
+                "{~ import \"kit/_macros.html\" as ui }\
+                 {~ ui.button \"Buy\" shop } \
+                 {~ ui.button style = \"ghost\" \"Home & more\" \"/\" }"
+            )
+        );
+    }
+
+    #[test]
+    fn macro_calls_report_errors() {
+        let mut ctx = Context::new();
+        register_part(
+            &mut ctx,
+            "_macros.html",
+            "{~ macro pill(label) }\n<b>{~ get label | shout }</b>{~ end }",
+        );
+        let processor = TemplateProcessor::default();
+
+        let diagnostic = diagnose(
+            &processor,
+            &ctx,
+            "{~ import \"_macros.html\" as ui }{~ ui.pill \"a\" }",
+        );
+        assert_eq!("unknown template filter: shout", diagnostic.message);
+        assert_eq!("_macros.html", diagnostic.location.path);
+        assert_eq!(2, diagnostic.location.line);
+        assert_eq!("page.html", diagnostic.include_stack[0].path);
+
+        assert_eq!(
+            "missing argument 'label' to macro pill",
+            diagnose(
+                &processor,
+                &ctx,
+                "{~ import \"_macros.html\" as ui }{~ ui.pill }"
+            )
+            .message
+        );
+        assert_eq!(
+            "macro not found: nope in _macros.html",
+            diagnose(
+                &processor,
+                &ctx,
+                "{~ import \"_macros.html\" as ui }{~ ui.nope }"
+            )
+            .message
+        );
+        assert_eq!(
+            "unknown template function: ui.pill",
+            diagnose(&processor, &ctx, "{~ ui.pill \"a\" }").message
+        );
+    }
+
+    #[test]
+    fn get_escapes_html() {
+        let ctx: Context = [(
+            "title".into(),
+            ContextValue::Text(r#"Tom & "Jerry" <3"#.into()),
+        )]
+        .into();
+
+        // Text positions escape markup characters.
+        let mut asset = Asset::new(
+            "page.html".into(),
+            r#"<h1>{~ get title}</h1>"#.as_bytes().to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(
+            asset.as_text().unwrap(),
+            r#"<h1>Tom &amp; "Jerry" &lt;3</h1>"#
+        );
+
+        // Attribute positions also escape quotes.
+        let mut asset = Asset::new(
+            "page.html".into(),
+            r#"<meta content="{~ get title}"> {~ get title}"#.as_bytes().to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(
+            asset.as_text().unwrap(),
+            r#"<meta content="Tom &amp; &quot;Jerry&quot; &lt;3"> Tom &amp; "Jerry" &lt;3"#
+        );
+
+        // Dates are escaped like any other output.
+        let ctx: Context = [("d".into(), ContextValue::Text("<soon>".into()))].into();
+        let mut asset = Asset::new("page.html".into(), r#"{~ date d "%Y"}"#.as_bytes().to_vec());
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "&lt;soon&gt;");
+    }
+
+    #[test]
+    fn raw_skips_escaping() {
+        let ctx: Context = [
+            (
+                "content".into(),
+                ContextValue::Text("<p>Hi & bye</p>".into()),
+            ),
+            ("title".into(), ContextValue::Text("A & B".into())),
+        ]
+        .into();
+
+        let mut asset = Asset::new(
+            "page.html".into(),
+            r#"{~ raw content}|{~ get content | raw}|{~ raw missing or content | upper}|{~ get title | escape}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(
+            asset.as_text().unwrap(),
+            "<p>Hi & bye</p>|<p>Hi & bye</p>|<P>HI & BYE</P>|A &amp; B"
+        );
+    }
+
+    #[test]
+    fn get_does_not_escape_non_html() {
+        let ctx: Context = [("color".into(), ContextValue::Text("\"a&b\" > c".into()))].into();
+
+        for path in ["style.css", "script.js"] {
+            let mut asset = Asset::new(path.into(), r#"{~ get color}"#.as_bytes().to_vec());
+            run(&ctx, &mut asset);
+            assert_eq!(asset.as_text().unwrap(), "\"a&b\" > c", "{}", path);
+        }
+    }
+
+    #[test]
+    fn get_escapes_markdown() {
+        let ctx: Context = [("title".into(), ContextValue::Text("<b>\"A\"</b>".into()))].into();
+
+        let mut asset = Asset::new(
+            "page.md".into(),
+            r#"# {~ get title}
+
+<a title="{~ get title}">x</a>"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(
+            asset.as_text().unwrap(),
+            r#"# &lt;b&gt;"A"&lt;/b&gt;
+
+<a title="&lt;b&gt;&quot;A&quot;&lt;/b&gt;">x</a>"#
+        );
+    }
+
+    #[test]
+    fn nested_blocks_escape_attributes() {
+        let mut ctx: Context = [("title".into(), ContextValue::Text("\"A\"".into()))].into();
+        register_part(&mut ctx, "_attrs.html", r#"{~ slot} {~ slot extra}"#);
+
+        // Output of blocks opened inside a tag is escaped as attributes.
+        let mut asset = Asset::new(
+            "page.html".into(),
+            r#"<a title="{~ capture t}{~ get title}{~ end}{~ raw t}"><b {~ use "_attrs.html"}title="{~ get title}"{~ fill extra}lang="{~ get title}"{~ end}{~ end}>"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(
+            asset.as_text().unwrap(),
+            r#"<a title="&quot;A&quot;"><b title="&quot;A&quot;" lang="&quot;A&quot;">"#
+        );
+    }
+
+    #[test]
+    fn set_assigns_variables() {
+        let ctx: Context = [
+            ("name".into(), ContextValue::Text("Aer Site".into())),
+            (
+                "tags".into(),
+                ContextValue::List(vec![ContextValue::Text("a".into())]),
+            ),
+        ]
+        .into();
+
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ set heading = title or name or "Home" | upper}{~ set greeting = "hi"}{~ set list = tags}{~ get heading} {~ get greeting} {~ get list | join "+"}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "AER SITE hi a");
+
+        // Later assignments replace earlier ones, and missing values are empty.
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ set x = "1"}{~ set x = "2"}{~ get x}{~ set y = missing}[{~ get y}]"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "2[]");
+    }
+
+    #[test]
+    fn set_is_scoped_to_loops_and_parts() {
+        let mut ctx: Context = [(
+            "items".into(),
+            ContextValue::List(vec![
+                ContextValue::Text("a".into()),
+                ContextValue::Text("b".into()),
+            ]),
+        )]
+        .into();
+        register_part(&mut ctx, "_part.html", r#"{~ set x = "part"}{~ get x}"#);
+
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ set x = "outer"}{~ for item in items}{~ set x = item}{~ get x}{~ end},{~ use "_part.html"},{~ get x}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "ab,part,outer");
+    }
+
+    #[test]
+    fn set_rejects_malformed_expressions() {
+        let lctx = LayeredContext::from_flat(Context::default());
+        for template in [
+            r#"{~ set x}"#,
+            r#"{~ set x "1"}"#,
+            r#"{~ set x =}"#,
+            r#"{~ set a.b = "1"}"#,
+            r#"{~ set x = "1" or}"#,
+            r#"{~ set x = "1" and "2"}"#,
+        ] {
+            let mut asset = Asset::new("test.html".into(), template.as_bytes().to_vec());
+            assert!(
+                TemplateProcessor::default()
+                    .process(&Environment::test(), &lctx, &mut asset)
+                    .is_err(),
+                "{}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn capture_renders_block_into_variable() {
+        let mut ctx: Context = [
+            ("name".into(), ContextValue::Text("Ray".into())),
+            ("show".into(), ContextValue::Text("true".into())),
+        ]
+        .into();
+        register_part(&mut ctx, "_card.html", r#"<div>{~ raw body}</div>"#);
+
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ capture body}<b>Hi, {~ get name}</b>{~ if show}!{~ end}{~ end}{~ use "_card.html" with body as body}|{~ raw body}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(
+            asset.as_text().unwrap(),
+            "<div><b>Hi, Ray</b>!</div>|<b>Hi, Ray</b>!"
+        );
+
+        // Captures nest inside other blocks, scoped like `set`.
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"before{~ if show}{~ capture x}{~ get name}{~ end}[{~ get x}]{~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "before[Ray]");
+
+        // Variables set inside a capture don't leak out of it.
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ set name = "Inner"}{~ capture x}{~ set name = "Captured"}{~ set y = "leak"}{~ get name}{~ end}{~ raw x} {~ get name}{~ if y}{~ get y}{~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "Captured Inner");
+    }
+
+    /// Renders `{~ if <condition>}yes{~ else}no{~ end}` against `ctx`.
+    fn check_condition(ctx: &Context, condition: &str) -> String {
+        let template = format!("{{~ if {}}}yes{{~ else}}no{{~ end}}", condition);
+        let mut asset = Asset::new("test.html".into(), template.into_bytes());
+        run(ctx, &mut asset);
+        asset.as_text().unwrap().to_string()
+    }
+
+    fn condition_context() -> Context {
+        [
+            ("draft".into(), ContextValue::Text("false".into())),
+            ("featured".into(), ContextValue::Text("true".into())),
+            ("count".into(), ContextValue::Text("12".into())),
+            ("date".into(), ContextValue::Text("2025-04-17".into())),
+            (
+                "cutoff".into(),
+                ContextValue::Text("2025-01-01T00:00:00Z".into()),
+            ),
+            ("role".into(), ContextValue::Text("editor".into())),
+            (
+                "tags".into(),
+                ContextValue::List(vec![
+                    ContextValue::Text("games".into()),
+                    ContextValue::Text("rust".into()),
+                ]),
+            ),
+        ]
+        .into()
+    }
+
+    #[test]
+    fn if_boolean_operators() {
+        let ctx = condition_context();
+        for (condition, expected) in [
+            ("featured and not draft", "yes"),
+            ("featured and draft", "no"),
+            ("draft or featured", "yes"),
+            ("draft or missing", "no"),
+            // `and` binds tighter than `or`.
+            ("featured or draft and missing", "yes"),
+            ("(featured or draft) and missing", "no"),
+            ("not (draft or missing)", "yes"),
+            ("role is \"admin\" or role is \"editor\"", "yes"),
+            ("role is not \"admin\" and featured", "yes"),
+        ] {
+            assert_eq!(check_condition(&ctx, condition), expected, "{}", condition);
+        }
+    }
+
+    #[test]
+    fn if_ordered_comparisons() {
+        let ctx = condition_context();
+        for (condition, expected) in [
+            // Numbers compare numerically, not lexicographically.
+            ("count > 9", "yes"),
+            ("count < 9", "no"),
+            ("count >= 12", "yes"),
+            ("count <= 11.5", "no"),
+            // Dates compare chronologically, with or without times.
+            ("date > cutoff", "yes"),
+            ("date <= \"2024-12-31\"", "no"),
+            // Anything else compares lexicographically.
+            ("role < \"fan\"", "yes"),
+            // Missing values never compare.
+            ("missing < 1", "no"),
+            ("missing >= 1", "no"),
+        ] {
+            assert_eq!(check_condition(&ctx, condition), expected, "{}", condition);
+        }
+    }
+
+    #[test]
+    fn if_membership() {
+        let ctx = condition_context();
+        for (condition, expected) in [
+            ("\"games\" in tags", "yes"),
+            ("\"art\" in tags", "no"),
+            ("\"art\" not in tags", "yes"),
+            ("not \"games\" in tags", "no"),
+            ("\"dit\" in role", "yes"),
+            ("\"games\" in missing", "no"),
+            ("\"rust\" in tags and count > 10", "yes"),
+        ] {
+            assert_eq!(check_condition(&ctx, condition), expected, "{}", condition);
+        }
+    }
+
+    #[test]
+    fn if_rejects_malformed_conditions() {
+        let lctx = LayeredContext::from_flat(condition_context());
+        for condition in [
+            "featured and",
+            "(featured or draft",
+            "featured draft",
+            "count >",
+            "and featured",
+            "featured )",
+        ] {
+            let template = format!("{{~ if {}}}yes{{~ end}}", condition);
+            let mut asset = Asset::new("test.html".into(), template.into_bytes());
+            assert!(
+                TemplateProcessor::default()
+                    .process(&Environment::test(), &lctx, &mut asset)
+                    .is_err(),
+                "{}",
+                condition
+            );
+        }
+    }
+
+    #[test]
+    fn use_block_fills_default_slot() {
+        let mut ctx: Context = [
+            ("name".into(), ContextValue::Text("Ray".into())),
+            ("title".into(), ContextValue::Text("Card".into())),
+        ]
+        .into();
+        register_part(
+            &mut ctx,
+            "_card.html",
+            r#"title = "Default"
+
+***
+<div class="card"><h2>{~ get title}</h2>{~ slot}</div>"#,
+        );
+
+        // Block content renders in the caller's scope, where `title` is "Card".
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ use "_card.html"}<p>Hi, {~ get name} ({~ get title})</p>{~ end}!"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(
+            asset.as_text().unwrap(),
+            "\n<div class=\"card\"><h2>Default</h2><p>Hi, Ray (Card)</p></div>!"
+        );
     }
 
     #[test]
-    fn deeply_nested_dotted_access() {
-        let mut asset = Asset::new("test.html".into(), r#"{~ get a.b.c}"#.as_bytes().to_vec());
+    fn use_block_fills_named_slots() {
+        let mut ctx: Context = [
+            ("show".into(), ContextValue::Text("true".into())),
+            (
+                "items".into(),
+                ContextValue::List(vec![
+                    ContextValue::Text("a".into()),
+                    ContextValue::Text("b".into()),
+                ]),
+            ),
+        ]
+        .into();
+        register_part(
+            &mut ctx,
+            "_callout.html",
+            r#"<aside><header>{~ slot header}</header>{~ slot}<footer>{~ slot footer}</footer></aside>"#,
+        );
 
-        let mut inner = Context::default();
-        inner.insert("c".into(), ContextValue::Text("deep".into()));
-        let mut outer = Context::default();
-        outer.insert("b".into(), ContextValue::Table(inner));
-        let ctx: Context = [("a".into(), ContextValue::Table(outer))].into();
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ for item in items}{~ use "_callout.html"}{~ fill header}<b>{~ get item}</b>{~ end}{~ if show}Body {~ get item}{~ end}{~ end}{~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
         run(&ctx, &mut asset);
-
-        assert_eq!(asset.as_text().unwrap(), "deep");
+        assert_eq!(
+            asset.as_text().unwrap(),
+            "<aside><header><b>a</b></header>Body a<footer></footer></aside>\
+             <aside><header><b>b</b></header>Body b<footer></footer></aside>"
+        );
     }
 
     #[test]
-    fn missing_dotted_path() {
+    fn use_blocks_nest() {
+        let mut ctx = Context::default();
+        register_part(&mut ctx, "_outer.html", r#"<outer>{~ slot}</outer>"#);
+        register_part(
+            &mut ctx,
+            "_inner.html",
+            r#"<inner>{~ slot title}:{~ slot}</inner>{~ use "_plain.html"}{~ end}"#,
+        );
+        register_part(&mut ctx, "_plain.html", r#"[{~ slot}]"#);
+
         let mut asset = Asset::new(
             "test.html".into(),
-            r#"{~ get user.missing}"#.as_bytes().to_vec(),
+            r#"{~ use "_outer.html"}{~ use "_inner.html"}{~ fill title}T{~ end}body{~ end}{~ end}"#
+                .as_bytes()
+                .to_vec(),
         );
-
-        let ctx = Context::default();
         run(&ctx, &mut asset);
 
-        assert_eq!(asset.as_text().unwrap(), "{~ get user.missing }");
+        // Slots of the outer part aren't visible to parts it uses.
+        assert_eq!(
+            asset.as_text().unwrap(),
+            "<outer><inner>T:body</inner>[]</outer>"
+        );
     }
 
     #[test]
-    fn get_fallback_uses_first_resolved() {
+    fn use_without_block_leaves_slots_empty() {
+        let mut ctx = Context::default();
+        register_part(&mut ctx, "_card.html", r#"<div>{~ slot}</div>"#);
+
         let mut asset = Asset::new(
             "test.html".into(),
-            r#"{~ get title or name}"#.as_bytes().to_vec(),
+            r#"{~ use "_card.html"}!"#.as_bytes().to_vec(),
         );
-
-        let ctx: Context = [("name".into(), ContextValue::Text("Alice".into()))].into();
         run(&ctx, &mut asset);
-
-        assert_eq!(asset.as_text().unwrap(), "Alice");
+        assert_eq!(asset.as_text().unwrap(), "<div></div>!");
     }
 
     #[test]
-    fn get_fallback_prefers_first() {
+    fn block_use_of_missing_part_reports_the_part() {
+        let lctx = LayeredContext::from_flat(Context::default());
         let mut asset = Asset::new(
             "test.html".into(),
-            r#"{~ get title or name}"#.as_bytes().to_vec(),
+            r#"{~ use "_missing.html"}x{~ end}"#.as_bytes().to_vec(),
         );
-
-        let ctx: Context = [
-            ("title".into(), ContextValue::Text("Hello".into())),
-            ("name".into(), ContextValue::Text("Alice".into())),
-        ]
-        .into();
-        run(&ctx, &mut asset);
-
-        assert_eq!(asset.as_text().unwrap(), "Hello");
+        let error = TemplateProcessor::default()
+            .process(&Environment::test(), &lctx, &mut asset)
+            .unwrap_err();
+        assert!(format!("{error:?}").contains("_missing.html"));
     }
 
     #[test]
-    fn get_fallback_chain() {
+    fn fill_outside_use_block_is_an_error() {
+        let lctx = LayeredContext::from_flat(Context::default());
         let mut asset = Asset::new(
             "test.html".into(),
-            r#"{~ get a or b or c}"#.as_bytes().to_vec(),
+            r#"a{~ fill header}x{~ end}"#.as_bytes().to_vec(),
         );
-
-        let ctx: Context = [("c".into(), ContextValue::Text("third".into()))].into();
-        run(&ctx, &mut asset);
-
-        assert_eq!(asset.as_text().unwrap(), "third");
+        let result = TemplateProcessor::default().process(&Environment::test(), &lctx, &mut asset);
+        assert!(result.is_err());
     }
 
     #[test]
-    fn get_fallback_none_resolved() {
+    fn date_applies_filters() {
         let mut asset = Asset::new(
             "test.html".into(),
-            r#"{~ get title or name}"#.as_bytes().to_vec(),
+            r#"{~ date d "%B %Y" | upper}"#.as_bytes().to_vec(),
         );
 
-        let ctx = Context::default();
+        let ctx: Context = [("d".into(), ContextValue::Text("2025-04-17".into()))].into();
         run(&ctx, &mut asset);
-
-        assert_eq!(asset.as_text().unwrap(), "{~ get title or name }");
+        assert_eq!(asset.as_text().unwrap(), "APRIL 2025");
     }
 
     #[test]
-    fn get_fallback_with_dotted() {
-        let mut asset = Asset::new(
-            "test.html".into(),
-            r#"{~ get page.title or site.name}"#.as_bytes().to_vec(),
+    fn use_with_filtered_params() {
+        let content =
+            r#"{~ use "_tag.html", with title | slugify as slug, with "x" | upper as label}"#;
+        let mut asset = Asset::new("page.html".into(), content.as_bytes().to_vec());
+        let mut ctx = Context::default();
+        ctx.insert("title".into(), ContextValue::Text("My Post".into()));
+
+        let part_key: Text = format!("{}_tag.html", PART_CONTEXT_PREFIX).into();
+        ctx.insert(
+            part_key,
+            ContextValue::Text("<a href=\"/{~ get slug}\">{~ get label}</a>".into()),
         );
 
-        let mut site = Context::default();
-        site.insert("name".into(), ContextValue::Text("My Site".into()));
-        let ctx: Context = [("site".into(), ContextValue::Table(site))].into();
         run(&ctx, &mut asset);
-
-        assert_eq!(asset.as_text().unwrap(), "My Site");
+        assert_eq!(asset.as_text().unwrap(), "<a href=\"/my-post\">X</a>");
     }
 
     #[test]
@@ -1140,7 +3049,7 @@ a delimiter in it"#;
         let ctx = Context::default();
 
         let lctx = LayeredContext::from_flat(ctx);
-        let result = TemplateProcessor.process(&Environment::test(), &lctx, &mut asset);
+        let result = TemplateProcessor::default().process(&Environment::test(), &lctx, &mut asset);
         assert!(result.is_err());
     }
 
@@ -1518,6 +3427,104 @@ prod = "https://example.com"
         assert_eq!(asset.as_text().unwrap(), "yes");
     }
 
+    #[test]
+    fn if_else_renders_else_branch() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ if admin}admin{~ else}guest{~ end}"#.as_bytes().to_vec(),
+        );
+
+        let ctx: Context = [("admin".into(), ContextValue::Text("false".into()))].into();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "guest");
+    }
+
+    #[test]
+    fn if_else_renders_if_branch() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ if admin}admin{~ else}guest{~ end}"#.as_bytes().to_vec(),
+        );
+
+        let ctx: Context = [("admin".into(), ContextValue::Text("true".into()))].into();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "admin");
+    }
+
+    #[test]
+    fn else_if_chain_with_comparisons() {
+        let template = r#"{~ if role is "admin"}A{~ else if role is "editor"}E{~ else if not role}N{~ else}?{~ end}"#;
+
+        for (role, expected) in [
+            (Some("admin"), "A"),
+            (Some("editor"), "E"),
+            (None, "N"),
+            (Some("viewer"), "?"),
+        ] {
+            let mut asset = Asset::new("test.html".into(), template.as_bytes().to_vec());
+            let mut ctx = Context::default();
+            if let Some(role) = role {
+                ctx.insert("role".into(), ContextValue::Text(role.into()));
+            }
+            run(&ctx, &mut asset);
+
+            assert_eq!(asset.as_text().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn else_if_without_else_renders_nothing() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"before{~ if a}A{~ else if b}B{~ end}after"#.as_bytes().to_vec(),
+        );
+
+        let ctx = Context::default();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "beforeafter");
+    }
+
+    #[test]
+    fn else_with_nested_blocks() {
+        let mut asset = Asset::new(
+            "test.html".into(),
+            r#"{~ if empty}none{~ else}{~ for item in items}{~ if item is "b"}[b]{~ else}{~ get item}{~ end}{~ end}{~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+
+        let ctx: Context = [(
+            "items".into(),
+            ContextValue::List(vec![
+                ContextValue::Text("a".into()),
+                ContextValue::Text("b".into()),
+                ContextValue::Text("c".into()),
+            ]),
+        )]
+        .into();
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "a[b]c");
+    }
+
+    #[test]
+    fn else_outside_if_is_an_error() {
+        let content = r#"{~ for item in items}{~ get item}{~ else}none{~ end}"#;
+        let mut asset = Asset::new("page.html".into(), content.as_bytes().to_vec());
+        let ctx: Context = [(
+            "items".into(),
+            ContextValue::List(vec![ContextValue::Text("a".into())]),
+        )]
+        .into();
+
+        let lctx = LayeredContext::from_flat(ctx);
+        let result = TemplateProcessor::default().process(&Environment::test(), &lctx, &mut asset);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn for_assets_query_empty_when_unknown_path() {
         let mut asset = Asset::new(
@@ -1723,6 +3730,229 @@ prod = "https://example.com"
         assert_eq!(asset.as_text().unwrap(), "Old, New, ");
     }
 
+    #[test]
+    fn for_assets_sort_numbers() {
+        let template = r#"{~ for item in assets "logs" sort weight}{~ get item.title}, {~ end}"#;
+        let mut asset = Asset::new("index.html".into(), template.as_bytes().to_vec());
+
+        let entries = [("Ten", Some(10.0)), ("None", None), ("Nine", Some(9.0))]
+            .into_iter()
+            .map(|(title, weight)| {
+                let mut entry = Context::default();
+                entry.insert("title".into(), ContextValue::Text(title.into()));
+                if let Some(weight) = weight {
+                    entry.insert("weight".into(), ContextValue::Number(weight));
+                }
+                ContextValue::Table(entry)
+            })
+            .collect();
+        let mut ctx = Context::default();
+        ctx.insert("_assets:logs".into(), ContextValue::List(entries));
+
+        run(&ctx, &mut asset);
+
+        assert_eq!(asset.as_text().unwrap(), "Nine, Ten, None, ");
+    }
+
+    /// Builds a context with `_assets:logs` entries of `(title, date, tags)`.
+    fn logs_context(entries: &[(&str, &str, &[&str])]) -> Context {
+        let entries = entries
+            .iter()
+            .map(|(title, date, tags)| {
+                let mut entry = Context::default();
+                entry.insert("title".into(), ContextValue::Text((*title).into()));
+                entry.insert("date".into(), ContextValue::Text((*date).into()));
+                entry.insert(
+                    "tags".into(),
+                    ContextValue::List(
+                        tags.iter()
+                            .map(|tag| ContextValue::Text((*tag).into()))
+                            .collect(),
+                    ),
+                );
+                ContextValue::Table(entry)
+            })
+            .collect();
+
+        let mut ctx = Context::default();
+        ctx.insert("_assets:logs".into(), ContextValue::List(entries));
+        ctx
+    }
+
+    #[test]
+    fn for_assets_limit_and_offset() {
+        let ctx = logs_context(&[
+            ("A", "2025-01-01", &[]),
+            ("B", "2025-02-01", &[]),
+            ("C", "2025-03-01", &[]),
+            ("D", "2025-04-01", &[]),
+        ]);
+
+        let mut asset = Asset::new(
+            "index.html".into(),
+            r#"{~ for item in assets "logs" sort date desc limit 2}{~ get item.title}, {~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "D, C, ");
+
+        // Clauses always apply in the order where, sort, offset, limit.
+        let mut asset = Asset::new(
+            "index.html".into(),
+            r#"{~ for item in assets "logs" limit 2 offset 1 sort date}{~ get item.title}, {~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "B, C, ");
+
+        // Offsets past the end produce nothing.
+        let mut asset = Asset::new(
+            "index.html".into(),
+            r#"{~ for item in assets "logs" offset 10}{~ get item.title}{~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "");
+    }
+
+    #[test]
+    fn for_assets_where_clauses() {
+        let ctx = logs_context(&[
+            ("A", "2025-01-01", &["rust", "web"]),
+            ("B", "2025-02-01", &["games"]),
+            ("C", "2025-03-01", &["rust"]),
+        ]);
+
+        let mut asset = Asset::new(
+            "index.html".into(),
+            r#"{~ for item in assets "logs" where tags contains "rust" sort date desc}{~ get item.title}, {~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "C, A, ");
+
+        let mut asset = Asset::new(
+            "index.html".into(),
+            r#"{~ for item in assets "logs" where title is "B"}{~ get item.title}{~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "B");
+
+        // Conditions compose, and may compare against context values.
+        let mut ctx = ctx;
+        ctx.insert("topic".into(), ContextValue::Text("rust".into()));
+        let mut asset = Asset::new(
+            "index.html".into(),
+            r#"{~ for item in assets "logs" where tags contains topic where title is not "A"}{~ get item.title}{~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "C");
+    }
+
+    #[test]
+    fn for_assets_group_clauses() {
+        let ctx = logs_context(&[
+            ("A", "2024-11-20", &["rust", "web"]),
+            ("B", "2025-02-01", &["games"]),
+            ("C", "2025-02-14", &["rust"]),
+            ("D", "undated", &[]),
+        ]);
+
+        // Groups are ordered by their first item, and list
+        // values put an item into the group of each value.
+        let mut asset = Asset::new(
+            "index.html".into(),
+            r#"{~ for group in assets "logs" sort title group by tags}{~ get group.key}: {~ for item in group.items}{~ get item.title}{~ end}; {~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "rust: AC; web: A; games: B; ");
+
+        // Dates group by year or month, skipping unparseable dates.
+        let mut asset = Asset::new(
+            "index.html".into(),
+            r#"{~ for group in assets "logs" sort date desc group by date year}{~ get group.key} ({~ get group.date}): {~ for item in group.items}{~ get item.title}{~ end}; {~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(
+            asset.as_text().unwrap(),
+            "2025 (2025-01-01): CB; 2024 (2024-01-01): A; "
+        );
+
+        let mut asset = Asset::new(
+            "index.html".into(),
+            r#"{~ for group in assets "logs" group by date month limit 2}{~ date group.date "%B %Y"}: {~ for item in group.items}{~ get item.title}{~ end}; {~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(
+            asset.as_text().unwrap(),
+            "November 2024: A; February 2025: B; "
+        );
+    }
+
+    #[test]
+    fn for_list_clauses() {
+        let mut ctx = logs_context(&[
+            ("A", "2025-01-01", &[]),
+            ("B", "2025-02-01", &[]),
+            ("C", "2025-03-01", &[]),
+        ]);
+        let entries = ctx.get(&"_assets:logs".into()).unwrap().clone();
+        ctx.insert("entries".into(), entries);
+        ctx.insert(
+            "names".into(),
+            ContextValue::List(vec![
+                ContextValue::Text("x".into()),
+                ContextValue::Text("y".into()),
+                ContextValue::Text("z".into()),
+            ]),
+        );
+
+        let mut asset = Asset::new(
+            "index.html".into(),
+            r#"{~ for item in entries where title is not "B" sort date desc}{~ get item.title}, {~ end}|{~ for name in names offset 1 limit 1}{~ get name}{~ end}"#
+                .as_bytes()
+                .to_vec(),
+        );
+        run(&ctx, &mut asset);
+        assert_eq!(asset.as_text().unwrap(), "C, A, |y");
+    }
+
+    #[test]
+    fn for_rejects_malformed_clauses() {
+        let ctx = logs_context(&[("A", "2025-01-01", &[])]);
+        let lctx = LayeredContext::from_flat(ctx);
+
+        for template in [
+            r#"{~ for item in assets "logs" limit}{~ end}"#,
+            r#"{~ for item in assets "logs" limit "many"}{~ end}"#,
+            r#"{~ for item in assets "logs" where title equals "A"}{~ end}"#,
+            r#"{~ for item in assets "logs" shuffle}{~ end}"#,
+        ] {
+            let mut asset = Asset::new("index.html".into(), template.as_bytes().to_vec());
+            assert!(
+                TemplateProcessor::default()
+                    .process(&Environment::test(), &lctx, &mut asset)
+                    .is_err(),
+                "{}",
+                template
+            );
+        }
+    }
+
     #[test]
     fn date_formats_value() {
         let mut asset = Asset::new(
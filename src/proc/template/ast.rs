@@ -0,0 +1,609 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use codas::types::Text;
+use codas::types::cryptography::CryptoHasher;
+use logos::{Lexer, Logos, Span};
+
+use super::tokenizer::{TemplateExpression, Token};
+
+/// Opening of a template expression which trims all whitespace before it.
+const TRIM_BEFORE_MARKER: &str = "{~-";
+
+/// Closing of a template expression which trims all whitespace after it.
+const TRIM_AFTER_MARKER: &str = "-}";
+
+/// Names of template functions which always open a block.
+const BLOCK_FUNCTIONS: &[&str] = &["if", "for", "capture", "fill", "verbatim", "block", "macro"];
+
+/// A template parsed into a tree of [Node]s.
+///
+/// Whitespace trimming is resolved during parsing, so rendering
+/// a template is just a walk over its nodes.
+#[derive(Debug)]
+pub(super) struct Template {
+    pub nodes: Vec<Node>,
+}
+
+/// A node of a parsed [Template].
+#[derive(Debug)]
+pub(super) enum Node {
+    /// Text copied directly into the output.
+    Text(String),
+
+    /// A template expression, and the block it opens (if any).
+    Expression(Expression),
+
+    /// An expression which couldn't be parsed, or which can't
+    /// appear where it does. Errors are only reported when (and
+    /// if) they're rendered, like any other expression.
+    Error { span: Span, message: Text },
+}
+
+/// A template function call parsed from a `{~ }` expression.
+#[derive(Debug)]
+pub(super) struct Expression {
+    /// Span of the expression in the template's source.
+    pub span: Span,
+
+    pub name: Text,
+
+    pub args: Vec<TemplateExpression>,
+
+    /// Branches of the block opened by the expression, if any.
+    pub block: Option<Vec<Branch>>,
+}
+
+/// A branch of a block, split from other branches
+/// by top-level `{~ else [if ...] }` clauses.
+#[derive(Debug)]
+pub(super) struct Branch {
+    /// Arguments of the `else` clause opening the branch. The first
+    /// branch is opened by the block's own expression, so its
+    /// arguments are always empty.
+    pub clause: Vec<TemplateExpression>,
+
+    pub nodes: Vec<Node>,
+}
+
+/// Cache of parsed [Template]s, keyed by a hash of
+/// their source and whether `trim_blocks` was set.
+#[derive(Debug, Default)]
+pub struct TemplateCache {
+    templates: Mutex<HashMap<[u8; 32], Arc<Template>>>,
+}
+
+impl TemplateCache {
+    /// Returns the template parsed from `source`, parsing it
+    /// if it hasn't been parsed with the same `trim_blocks`.
+    pub(super) fn get_or_parse(&self, source: &str, trim_blocks: bool) -> Arc<Template> {
+        let mut hasher = CryptoHasher::default();
+        hasher.write(&[trim_blocks as u8]);
+        hasher.write(source.as_bytes());
+        let key = hasher.finalize().into();
+        if let Some(template) = self.templates.lock().unwrap().get(&key) {
+            return template.clone();
+        }
+
+        // Parse outside the lock, so other templates can be parsed concurrently.
+        let template = Arc::new(Template::parse(source, trim_blocks));
+        self.templates.lock().unwrap().insert(key, template.clone());
+        template
+    }
+}
+
+impl Template {
+    /// Parses `source` into a template.
+    ///
+    /// If `trim_blocks` is true, the first newline after each expression
+    /// opening, splitting, or closing a block is removed from the output.
+    pub fn parse(source: &str, trim_blocks: bool) -> Self {
+        let mut parser = Parser {
+            lexer: Token::lexer(source),
+            trim_blocks,
+            block_uses: find_block_uses(source),
+        };
+
+        let (mut nodes, terminator) = parser.parse_nodes();
+        match terminator {
+            Terminator::Eof => {}
+            Terminator::Else(span, _) => nodes.push(Node::Error {
+                span,
+                message: "unexpected 'else' outside of an if block".into(),
+            }),
+            Terminator::End(span) => nodes.push(Node::Error {
+                span,
+                message: "unexpected end-of-block".into(),
+            }),
+        }
+
+        Self { nodes }
+    }
+}
+
+/// What ended a sequence of nodes parsed by [Parser::parse_nodes].
+enum Terminator {
+    /// The end of the source.
+    Eof,
+
+    /// An `else` clause at `span`, with its arguments.
+    Else(Span, Vec<TemplateExpression>),
+
+    /// An `end` expression at `span`.
+    End(Span),
+}
+
+/// Recursive-descent parser of [Template]s.
+struct Parser<'s> {
+    lexer: Lexer<'s, Token>,
+    trim_blocks: bool,
+
+    /// Offsets of the `use` expressions which open blocks
+    /// (see [find_block_uses]).
+    block_uses: HashSet<usize>,
+}
+
+impl Parser<'_> {
+    /// Parses nodes until the end of the source, or the
+    /// `else` or `end` expression closing the current branch.
+    fn parse_nodes(&mut self) -> (Vec<Node>, Terminator) {
+        let mut nodes = vec![];
+
+        // True if the last node is text which following text should be
+        // appended to, and whose trailing whitespace may still be trimmed.
+        let mut open_text = false;
+
+        loop {
+            // Tokens are contiguous, so each starts where the last one ended.
+            let start = self.lexer.span().end;
+            let Some(token) = self.lexer.next() else {
+                return (nodes, Terminator::Eof);
+            };
+            let span = start..self.lexer.span().end;
+
+            match token {
+                Ok(Token::OpenTemplate(expression)) => {
+                    // Trim the whitespace before the expression if requested.
+                    if open_text
+                        && self.lexer.source()[span.start..].starts_with(TRIM_BEFORE_MARKER)
+                        && let Some(Node::Text(text)) = nodes.last_mut()
+                    {
+                        text.truncate(text.trim_end().len());
+                    }
+                    open_text = false;
+
+                    let (name, args) = match expression {
+                        Ok(TemplateExpression::Function { name, args }) => (name, args),
+                        Ok(expression) => {
+                            let message =
+                                format!("unexpected template expression: {:?}", expression);
+                            nodes.push(Node::Error {
+                                span,
+                                message: message.into(),
+                            });
+                            continue;
+                        }
+                        Err(err) => {
+                            nodes.push(Node::Error {
+                                span,
+                                message: format!("template parse error: {}", err).into(),
+                            });
+                            continue;
+                        }
+                    };
+
+                    match name.as_str() {
+                        "else" => return (nodes, Terminator::Else(span, args)),
+                        "end" => return (nodes, Terminator::End(span)),
+                        "verbatim" if let Some(node) = self.parse_raw_verbatim() => {
+                            nodes.push(node);
+                            self.trim_after_expression(true);
+                        }
+                        _ if BLOCK_FUNCTIONS.contains(&name.as_str())
+                            || (name == "use" && self.block_uses.contains(&span.start)) =>
+                        {
+                            nodes.push(self.parse_block(span, name, args));
+                            self.trim_after_expression(true);
+                        }
+                        _ => {
+                            nodes.push(Node::Expression(Expression {
+                                span,
+                                name,
+                                args,
+                                block: None,
+                            }));
+                            self.trim_after_expression(false);
+                        }
+                    }
+                }
+
+                // Comments render nothing.
+                Ok(Token::Comment(Ok(()))) => {}
+                Ok(Token::Comment(Err(err))) => {
+                    nodes.push(Node::Error {
+                        span,
+                        message: err.into(),
+                    });
+                    open_text = false;
+                }
+
+                // If the lexer couldn't parse a token, the next value
+                // is just text we can copy directly into the output.
+                Err(_) => {
+                    let text = self.lexer.slice();
+                    match nodes.last_mut() {
+                        Some(Node::Text(last)) if open_text => last.push_str(text),
+                        _ => nodes.push(Node::Text(text.into())),
+                    }
+                    open_text = true;
+                }
+            }
+        }
+    }
+
+    /// Parses the branches of the block opened by the
+    /// expression at `span`, up to and including its `end`.
+    fn parse_block(&mut self, span: Span, name: Text, args: Vec<TemplateExpression>) -> Node {
+        let mut branches = vec![];
+        let mut clause = vec![];
+        let content_start = self.branch_start();
+
+        loop {
+            let (nodes, terminator) = self.parse_nodes();
+            match terminator {
+                Terminator::Else(_, args) => {
+                    branches.push(Branch {
+                        clause: std::mem::replace(&mut clause, args),
+                        nodes,
+                    });
+                    self.branch_start();
+                }
+
+                Terminator::End(end) => {
+                    branches.push(Branch { clause, nodes });
+
+                    // Verbatim blocks keep their content as-is.
+                    if name == "verbatim" {
+                        let source = self.lexer.source();
+                        let mut content = &source[content_start..end.start];
+                        if source[end.start..].starts_with(TRIM_BEFORE_MARKER) {
+                            content = content.trim_end();
+                        }
+                        return Node::Text(content.into());
+                    }
+
+                    return Node::Expression(Expression {
+                        span,
+                        name,
+                        args,
+                        block: Some(branches),
+                    });
+                }
+
+                Terminator::Eof => {
+                    return Node::Error {
+                        span,
+                        message: "unclosed block; expected a matching '{~ end}'".into(),
+                    };
+                }
+            }
+        }
+    }
+
+    /// Parses the content of a `verbatim` block closed by `{~ end verbatim}`
+    /// as raw text, up to and including the closing expression. Returns
+    /// `None` if the block isn't closed that way, so it's parsed like
+    /// any other block, and closed by its matching `{~ end}`.
+    fn parse_raw_verbatim(&mut self) -> Option<Node> {
+        let remainder = self.lexer.remainder();
+        let end = find_end_verbatim(remainder)?;
+
+        let trimmed = {
+            self.branch_start();
+            remainder.len() - self.lexer.remainder().len()
+        };
+        let mut content = &remainder[trimmed..end.start];
+        if remainder[end.start..].starts_with(TRIM_BEFORE_MARKER) {
+            content = content.trim_end();
+        }
+        self.lexer.bump(end.end - trimmed);
+        Some(Node::Text(content.into()))
+    }
+
+    /// Trims the whitespace at the start of a block branch (as requested
+    /// by the expression opening it), returning the start of the branch.
+    fn branch_start(&mut self) -> usize {
+        self.trim_after_expression(true);
+        self.lexer.span().end
+    }
+
+    /// Trims the whitespace after the last expression if it has a trim
+    /// marker or, if [Self::trim_blocks] is set, the newline after it
+    /// if the expression opened, split, or closed a `block`.
+    fn trim_after_expression(&mut self, block: bool) {
+        let remainder = self.lexer.remainder();
+        if self.lexer.source()[..self.lexer.span().end].ends_with(TRIM_AFTER_MARKER) {
+            self.lexer
+                .bump(remainder.len() - remainder.trim_start().len());
+        } else if block && self.trim_blocks {
+            self.lexer.bump(leading_newline(remainder));
+        }
+    }
+}
+
+/// An expression affecting the structure of blocks in a template.
+#[derive(Clone, Copy)]
+enum BlockEvent {
+    /// An expression which always opens a block.
+    Open,
+
+    /// A `use` expression at an offset, which may open a block.
+    Use(usize),
+
+    /// An `else` clause.
+    Else,
+
+    /// An `end` expression.
+    End,
+}
+
+/// Returns the offsets in `source` of the `use` expressions which open
+/// blocks, like `{~ use "_card.html"}...{~ end}`.
+///
+/// A `use` expression is inline unless an `{~ end}` can only be matched by
+/// it: each `{~ end}` closes the nearest open block which isn't a `use`
+/// (leaving any `use` expressions since it inline), unless the rest of
+/// `source` couldn't close the blocks still open after it; then, it closes
+/// the nearest `use`. This way, `{~ if a}{~ use "_x.html"}{~ end}` uses
+/// `_x.html` inline, while `{~ if a}{~ use "_x.html"}x{~ end}{~ end}` uses
+/// it as a block. `{~ else}` clauses are never part of a `use` block.
+fn find_block_uses(source: &str) -> HashSet<usize> {
+    let mut events = vec![];
+    let mut lexer = Token::lexer(source);
+    loop {
+        // Expressions start where the last token ended, as in parsing.
+        let start = lexer.span().end;
+        let Some(token) = lexer.next() else {
+            break;
+        };
+        let Ok(Token::OpenTemplate(Ok(TemplateExpression::Function { name, .. }))) = token else {
+            continue;
+        };
+        let event = match name.as_str() {
+            "end" => BlockEvent::End,
+            "else" => BlockEvent::Else,
+            "use" => BlockEvent::Use(start),
+            "verbatim" if let Some(end) = find_end_verbatim(lexer.remainder()) => {
+                lexer.bump(end.end);
+                continue;
+            }
+            name if BLOCK_FUNCTIONS.contains(&name) => BlockEvent::Open,
+            _ => continue,
+        };
+        events.push(event);
+    }
+
+    // The least and most `{~ end}`s following each event which close
+    // blocks opened before it: the least if each following `use` opens
+    // a block closed by the nearest `{~ end}` possible, and the most if
+    // none of them do.
+    let mut unmatched_ends = vec![(0usize, 0usize); events.len() + 1];
+    for (i, event) in events.iter().enumerate().rev() {
+        let (least, most) = unmatched_ends[i + 1];
+        unmatched_ends[i] = match event {
+            BlockEvent::Open => (least.saturating_sub(1), most.saturating_sub(1)),
+            BlockEvent::Use(_) => (least.saturating_sub(1), most),
+            BlockEvent::Else => (least, most),
+            BlockEvent::End => (least + 1, most + 1),
+        };
+    }
+
+    // Open blocks, with the offset of each `use` that may open one.
+    let mut open: Vec<Option<usize>> = vec![];
+    let mut block_uses = HashSet::new();
+    for (i, event) in events.into_iter().enumerate() {
+        match event {
+            BlockEvent::Open => open.push(None),
+            BlockEvent::Use(offset) => open.push(Some(offset)),
+            BlockEvent::Else => {
+                while let Some(Some(_)) = open.last() {
+                    open.pop();
+                }
+            }
+            BlockEvent::End => {
+                // Close the nearest block which isn't a `use`, if the
+                // blocks still open after it can be closed by the rest.
+                let (least, most) = unmatched_ends[i + 1];
+                let nearest = open.iter().rposition(Option::is_none);
+                let closes_nearest = nearest.is_some_and(|nearest| {
+                    let still_open = &open[..nearest];
+                    let required = still_open.iter().filter(|block| block.is_none()).count();
+                    least <= still_open.len() && most >= required
+                });
+
+                match (open.last(), nearest) {
+                    (Some(Some(offset)), _) if !closes_nearest => {
+                        block_uses.insert(*offset);
+                        open.pop();
+                    }
+                    (_, Some(nearest)) => open.truncate(nearest),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    block_uses
+}
+
+/// Returns the span of the first `{~ end verbatim}` expression in `text`,
+/// unless another `verbatim` block is opened before it.
+fn find_end_verbatim(text: &str) -> Option<Span> {
+    for (start, _) in text.match_indices("{~") {
+        let expression = &text[start + 2..];
+        let expression = expression
+            .strip_prefix('-')
+            .unwrap_or(expression)
+            .trim_start();
+        if strip_keyword(expression, "verbatim").is_some() {
+            return None;
+        }
+        let Some(rest) = strip_keyword(expression, "end")
+            .and_then(|rest| strip_keyword(rest.trim_start(), "verbatim"))
+        else {
+            continue;
+        };
+
+        let rest = rest.trim_start();
+        let close = if rest.starts_with(TRIM_AFTER_MARKER) {
+            TRIM_AFTER_MARKER.len()
+        } else if rest.starts_with('}') {
+            1
+        } else {
+            continue;
+        };
+        return Some(start..text.len() - rest.len() + close);
+    }
+    None
+}
+
+/// Returns the rest of `text` after the identifier `keyword`
+/// at its start, or `None` if `text` doesn't start with it.
+fn strip_keyword<'t>(text: &'t str, keyword: &str) -> Option<&'t str> {
+    text.strip_prefix(keyword)
+        .filter(|rest| !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_' || c == '.'))
+}
+
+/// Returns the length of the newline at the start of `text`, if any.
+fn leading_newline(text: &str) -> usize {
+    if text.starts_with("\r\n") {
+        2
+    } else if text.starts_with('\n') {
+        1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Template {
+        Template::parse(source, false)
+    }
+
+    #[test]
+    fn caches_templates_by_source_and_trimming() {
+        let cache = TemplateCache::default();
+        let template = cache.get_or_parse("a{~ if x}\nb{~ end}", false);
+        assert!(Arc::ptr_eq(
+            &template,
+            &cache.get_or_parse("a{~ if x}\nb{~ end}", false)
+        ));
+        assert!(!Arc::ptr_eq(
+            &template,
+            &cache.get_or_parse("a{~ if x}\nb{~ end}", true)
+        ));
+        assert!(!Arc::ptr_eq(&template, &cache.get_or_parse("a", false)));
+    }
+
+    #[test]
+    fn parses_nested_blocks() {
+        let template = parse("a{~ if x}b{~ for y in z}c{~ end}{~ else}d{~ end}e");
+        let [Node::Text(a), Node::Expression(if_block), Node::Text(e)] = &template.nodes[..] else {
+            panic!("unexpected nodes: {:?}", template.nodes);
+        };
+        assert_eq!(("a", "e"), (a.as_str(), e.as_str()));
+        assert_eq!("if", if_block.name);
+        assert_eq!(1..9, if_block.span);
+
+        let branches = if_block.block.as_ref().unwrap();
+        assert_eq!(2, branches.len());
+        assert!(matches!(
+            &branches[0].nodes[..],
+            [
+                Node::Text(_),
+                Node::Expression(Expression { block: Some(_), .. })
+            ]
+        ));
+        assert!(matches!(&branches[1].nodes[..], [Node::Text(d)] if d == "d"));
+    }
+
+    #[test]
+    fn resolves_whitespace_trimming() {
+        let template = parse("a \n{~- get x -}\n b{~ verbatim -}\n {~ get y} {~- end}");
+        let [
+            Node::Text(a),
+            Node::Expression(_),
+            Node::Text(b),
+            Node::Text(verbatim),
+        ] = &template.nodes[..]
+        else {
+            panic!("unexpected nodes: {:?}", template.nodes);
+        };
+        assert_eq!(
+            ("a", "b", "{~ get y}"),
+            (a.as_str(), b.as_str(), verbatim.as_str())
+        );
+    }
+
+    #[test]
+    fn finds_block_uses() {
+        // Returns whether each `use` expression in `source` opens a block.
+        let block_uses = |source: &str| -> Vec<bool> {
+            let block_uses = find_block_uses(source);
+            source
+                .match_indices("{~ use")
+                .map(|(offset, _)| block_uses.contains(&offset))
+                .collect()
+        };
+
+        assert_eq!(block_uses(r#"{~ use "a"}"#), [false]);
+        assert_eq!(block_uses(r#"{~ use "a"}x{~ end}"#), [true]);
+        assert_eq!(block_uses(r#"{~ if a}{~ use "a"}{~ end}"#), [false]);
+        assert_eq!(block_uses(r#"{~ if a}{~ use "a"}x{~ end}{~ end}"#), [true]);
+        assert_eq!(
+            block_uses(r#"{~ for i in l}{~ use "a"}{~ end}{~ use "b"}x{~ end}"#),
+            [false, true]
+        );
+        assert_eq!(
+            block_uses(r#"{~ use "a"}{~ use "b"}x{~ end}{~ end}"#),
+            [true, true]
+        );
+        assert_eq!(
+            block_uses(r#"{~ use "a"}{~ if b}{~ use "c"}{~ end}{~ end}"#),
+            [true, false]
+        );
+        assert_eq!(
+            block_uses(r#"{~ if a}{~ use "a"}{~ else}{~ use "b"}{~ end}"#),
+            [false, false]
+        );
+        assert_eq!(
+            block_uses(r#"{~ use "a"}{~ verbatim}{~ end}{~ end verbatim}{~ end}"#),
+            [true]
+        );
+
+        // Block uses are parsed as blocks.
+        let template = parse(r#"{~ use "a"}{~ use "b"}x{~ end}"#);
+        let [Node::Expression(inline), Node::Expression(block)] = &template.nodes[..] else {
+            panic!("unexpected nodes: {:?}", template.nodes);
+        };
+        assert!(inline.block.is_none());
+        assert!(block.block.is_some());
+    }
+
+    #[test]
+    fn defers_errors_to_rendering() {
+        let template = parse("{~ if x}{~ end}{~ for y in z}");
+        assert!(matches!(
+            &template.nodes[..],
+            [Node::Expression(_), Node::Error { message, .. }] if message.starts_with("unclosed block")
+        ));
+        let template = parse("{~ end}");
+        assert!(matches!(
+            &template.nodes[..],
+            [Node::Error { message, .. }] if message == "unexpected end-of-block"
+        ));
+    }
+}
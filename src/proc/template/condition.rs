@@ -0,0 +1,251 @@
+use std::cmp::Ordering;
+
+use super::TemplateProcessor;
+use super::tokenizer::TemplateExpression;
+use crate::proc::{ContextValue, Environment, LayeredContext, ProcessingError};
+
+/// Identifiers with special meaning inside conditions.
+const KEYWORDS: &[&str] = &["and", "or", "not", "is", "in"];
+
+/// Evaluates the condition of an `if` or `else if` clause:
+///
+/// ```text
+/// condition  := all ("or" all)*
+/// all        := unary ("and" unary)*
+/// unary      := "not" unary | "(" condition ")" | comparison
+/// comparison := value [("is" ["not"] | "<" | "<=" | ">" | ">=" | ["not"] "in") value]
+/// ```
+///
+/// A value without a comparison passes if it's truthy: present,
+/// non-empty, and not `false`, `0`, `"false"`, or `"0"`.
+pub(super) fn evaluate(
+    env: &Environment,
+    context: &LayeredContext,
+    args: &[TemplateExpression],
+) -> Result<bool, ProcessingError> {
+    if args.is_empty() {
+        return Err(ProcessingError::Compilation {
+            message: "missing condition in if expression".into(),
+        });
+    }
+
+    let mut parser = ConditionParser {
+        env,
+        context,
+        args,
+        position: 0,
+    };
+    let result = parser.parse_condition()?;
+
+    match parser.args.get(parser.position) {
+        None => Ok(result),
+        Some(arg) => Err(ProcessingError::Compilation {
+            message: format!("unexpected {:?} in condition", arg).into(),
+        }),
+    }
+}
+
+/// Returns true if `value` is truthy.
+fn is_truthy(value: Option<&ContextValue>) -> bool {
+    match value {
+        Some(ContextValue::Text(text)) => text != "false" && text != "0" && !text.is_empty(),
+        Some(ContextValue::Integer(integer)) => *integer != 0,
+        Some(ContextValue::Number(number)) => *number != 0.0,
+        Some(ContextValue::Bool(bool)) => *bool,
+        Some(ContextValue::Date(_) | ContextValue::AssetRef(_)) => true,
+        Some(ContextValue::List(list)) => !list.is_empty(),
+        Some(ContextValue::Table(table)) => !table.is_empty(),
+        None => false,
+    }
+}
+
+/// Recursive-descent evaluator for the grammar in [evaluate].
+///
+/// Conditions are evaluated as they're parsed, without short-circuiting,
+/// so that malformed conditions are always reported.
+struct ConditionParser<'a> {
+    env: &'a Environment,
+    context: &'a LayeredContext,
+    args: &'a [TemplateExpression],
+    position: usize,
+}
+
+impl<'a> ConditionParser<'a> {
+    fn parse_condition(&mut self) -> Result<bool, ProcessingError> {
+        let mut result = self.parse_all()?;
+        while self.eat("or") {
+            let rhs = self.parse_all()?;
+            result = result || rhs;
+        }
+        Ok(result)
+    }
+
+    fn parse_all(&mut self) -> Result<bool, ProcessingError> {
+        let mut result = self.parse_unary()?;
+        while self.eat("and") {
+            let rhs = self.parse_unary()?;
+            result = result && rhs;
+        }
+        Ok(result)
+    }
+
+    fn parse_unary(&mut self) -> Result<bool, ProcessingError> {
+        if self.eat("not") {
+            return Ok(!self.parse_unary()?);
+        }
+
+        if self.eat("(") {
+            let result = self.parse_condition()?;
+            if !self.eat(")") {
+                return Err(ProcessingError::Compilation {
+                    message: "missing ')' in condition".into(),
+                });
+            }
+            return Ok(result);
+        }
+
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<bool, ProcessingError> {
+        let lhs = self.parse_value()?;
+
+        let Some(operator) = self.peek_keyword() else {
+            return Ok(is_truthy(lhs.as_ref()));
+        };
+
+        match operator {
+            "is" => {
+                self.position += 1;
+                let negate = self.eat("not");
+                let rhs = self.parse_value()?;
+                let matches = match (lhs, rhs) {
+                    (Some(lhs), Some(rhs)) => TemplateProcessor::values_equal(self.env, &lhs, &rhs),
+                    _ => false,
+                };
+                Ok(matches != negate)
+            }
+
+            "in" => {
+                self.position += 1;
+                let rhs = self.parse_value()?;
+                Ok(self.contains(rhs.as_ref(), lhs.as_ref()))
+            }
+
+            "not" if self.peek_keyword_at(self.position + 1) == Some("in") => {
+                self.position += 2;
+                let rhs = self.parse_value()?;
+                Ok(!self.contains(rhs.as_ref(), lhs.as_ref()))
+            }
+
+            "<" | "<=" | ">" | ">=" => {
+                self.position += 1;
+                let rhs = self.parse_value()?;
+                let Some(ordering) = self.compare(lhs.as_ref(), rhs.as_ref()) else {
+                    return Ok(false);
+                };
+                Ok(match operator {
+                    "<" => ordering == Ordering::Less,
+                    "<=" => ordering != Ordering::Greater,
+                    ">" => ordering == Ordering::Greater,
+                    _ => ordering != Ordering::Less,
+                })
+            }
+
+            // Let the caller handle any other keywords (like `and`).
+            _ => Ok(is_truthy(lhs.as_ref())),
+        }
+    }
+
+    /// Parses and evaluates the next argument as a value,
+    /// returning `None` if the value isn't in the context.
+    fn parse_value(&mut self) -> Result<Option<ContextValue>, ProcessingError> {
+        let arg = self
+            .args
+            .get(self.position)
+            .ok_or(ProcessingError::Compilation {
+                message: "missing value in condition".into(),
+            })?;
+
+        if Self::keyword(arg).is_some() {
+            return Err(ProcessingError::Compilation {
+                message: format!("expected value in condition, got {:?}", arg).into(),
+            });
+        }
+
+        self.position += 1;
+        TemplateProcessor::evaluate_value(self.env, self.context, arg)
+    }
+
+    /// Returns true if `haystack` is a list containing `needle`, a table
+    /// with `needle` as a key, or text containing `needle`.
+    fn contains(&self, haystack: Option<&ContextValue>, needle: Option<&ContextValue>) -> bool {
+        let Some(needle) =
+            needle.and_then(|needle| TemplateProcessor::render_value(self.env, needle))
+        else {
+            return false;
+        };
+
+        match haystack {
+            Some(ContextValue::List(items)) => items.iter().any(|item| {
+                TemplateProcessor::render_value(self.env, item).as_ref() == Some(&needle)
+            }),
+            Some(ContextValue::Table(table)) => table.contains_key(&needle),
+            Some(ContextValue::Text(text)) => text.contains(needle.as_str()),
+            _ => false,
+        }
+    }
+
+    /// Orders two values numerically if both are numbers, chronologically
+    /// if both are dates, and lexicographically otherwise.
+    fn compare(&self, lhs: Option<&ContextValue>, rhs: Option<&ContextValue>) -> Option<Ordering> {
+        let (lhs, rhs) = (lhs?, rhs?);
+
+        if let (Some(lhs), Some(rhs)) = (
+            TemplateProcessor::value_number(lhs),
+            TemplateProcessor::value_number(rhs),
+        ) {
+            return lhs.partial_cmp(&rhs);
+        }
+
+        if let (Some(lhs), Some(rhs)) = (
+            TemplateProcessor::value_date(lhs),
+            TemplateProcessor::value_date(rhs),
+        ) {
+            return Some(lhs.cmp(&rhs));
+        }
+
+        let lhs = TemplateProcessor::render_value(self.env, lhs)?;
+        let rhs = TemplateProcessor::render_value(self.env, rhs)?;
+        Some(lhs.as_str().cmp(rhs.as_str()))
+    }
+
+    /// Consumes the next argument if it's the keyword `keyword`.
+    fn eat(&mut self, keyword: &str) -> bool {
+        if self.peek_keyword() == Some(keyword) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn peek_keyword(&self) -> Option<&'a str> {
+        self.peek_keyword_at(self.position)
+    }
+
+    fn peek_keyword_at(&self, position: usize) -> Option<&'a str> {
+        self.args.get(position).and_then(Self::keyword)
+    }
+
+    /// Returns the keyword or operator `arg` represents, if any.
+    fn keyword(arg: &'a TemplateExpression) -> Option<&'a str> {
+        match arg {
+            TemplateExpression::Identifier(id) if KEYWORDS.contains(&id.as_str()) => {
+                Some(id.as_str())
+            }
+            TemplateExpression::Operator(operator) => Some(operator.as_str()),
+            _ => None,
+        }
+    }
+}
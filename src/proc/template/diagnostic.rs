@@ -0,0 +1,260 @@
+use std::fmt;
+
+use codas::types::Text;
+use logos::Span;
+
+use super::inheritance::Blocks;
+use crate::proc::ProcessingError;
+
+/// A location within a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateLocation {
+    /// Path of the asset or part containing the location.
+    pub path: Text,
+
+    /// One-based line number.
+    pub line: usize,
+
+    /// One-based column number, in characters.
+    pub column: usize,
+}
+
+impl fmt::Display for TemplateLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.path, self.line, self.column)
+    }
+}
+
+/// An error in a template, annotated with where it occurred.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateDiagnostic {
+    pub message: Text,
+
+    /// Location of the start of the expression which failed.
+    pub location: TemplateLocation,
+
+    /// The line of source containing [Self::location].
+    pub source_line: Text,
+
+    /// Number of characters of the expression on [Self::source_line].
+    pub width: usize,
+
+    /// Locations of the `use` expressions through which the
+    /// template containing the error was included, innermost first.
+    pub include_stack: Vec<TemplateLocation>,
+}
+
+impl TemplateDiagnostic {
+    /// Attributes any locations within `from` to `to`, offset
+    /// by `line_offset` lines (e.g., of stripped frontmatter).
+    ///
+    /// Patterns are compiled under the path of the asset
+    /// they wrap, so their errors are relocated this way.
+    pub fn relocate(&mut self, from: &str, to: &str, line_offset: usize) {
+        for location in std::iter::once(&mut self.location).chain(&mut self.include_stack) {
+            if location.path == from {
+                location.path = to.into();
+                location.line += line_offset;
+            }
+        }
+    }
+}
+
+impl fmt::Display for TemplateDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.location, self.message)?;
+
+        // Align the caret with the expression, preserving tabs.
+        let gutter = " ".repeat(self.location.line.to_string().len());
+        let indent: String = self
+            .source_line
+            .chars()
+            .take(self.location.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", self.location.line, self.source_line)?;
+        write!(f, "{} | {}{}", gutter, indent, "^".repeat(self.width))?;
+
+        for location in &self.include_stack {
+            write!(f, "\n{} = included from {}", gutter, location)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A template source being compiled.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct TemplateFile<'a> {
+    /// Path of the asset or part the source came from.
+    pub path: &'a str,
+
+    /// The complete source, which all template spans index into.
+    pub source: &'a str,
+
+    /// Number of lines preceding the source in its file (e.g., frontmatter).
+    pub line_offset: usize,
+
+    /// True if output values should be escaped for HTML.
+    pub escape_html: bool,
+
+    /// True if the output enclosing the output rendered from this
+    /// source (e.g., around a `capture` block) ends inside an HTML tag.
+    pub in_tag: bool,
+
+    /// True if unresolved variables are errors.
+    pub strict: bool,
+
+    /// True if the newline after each block expression is removed.
+    pub trim_blocks: bool,
+
+    /// Blocks of the chain of templates this source is
+    /// part of, if it extends or is extended by another.
+    pub blocks: Option<&'a Blocks<'a>>,
+
+    /// Name and index (in [Self::blocks]) of the block
+    /// definition being rendered from this source, if any.
+    pub block: Option<(&'a Text, usize)>,
+}
+
+impl TemplateFile<'_> {
+    /// Returns the location of `offset` in this file.
+    pub fn locate(&self, offset: usize) -> TemplateLocation {
+        let before = &self.source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        TemplateLocation {
+            path: self.path.into(),
+            line: self.line_offset + before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    /// Annotates `error`, raised by the expression at `span`, with
+    /// its location in this file. Errors which were already annotated
+    /// by a nested expression are returned unchanged.
+    pub fn diagnose(&self, error: ProcessingError, span: Span) -> ProcessingError {
+        let message = match error {
+            ProcessingError::Template { .. } => return error,
+            ProcessingError::Compilation { message } => message,
+            error => error.to_string().into(),
+        };
+
+        let line_start = self.source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.source[span.start..]
+            .find('\n')
+            .map_or(self.source.len(), |i| span.start + i);
+        let width = self.source[span.start..span.end.min(line_end)]
+            .chars()
+            .count()
+            .max(1);
+
+        ProcessingError::Template {
+            diagnostic: Box::new(TemplateDiagnostic {
+                message,
+                location: self.locate(span.start),
+                source_line: self.source[line_start..line_end].into(),
+                width,
+                include_stack: vec![],
+            }),
+        }
+    }
+
+    /// Records that `error` occurred in a part included by the
+    /// `use` expression at `span` of this file.
+    pub fn include(&self, mut error: ProcessingError, span: Span) -> ProcessingError {
+        if let ProcessingError::Template { diagnostic } = &mut error {
+            diagnostic.include_stack.push(self.locate(span.start));
+        }
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagnoses_errors() {
+        let file = TemplateFile {
+            path: "index.html",
+            source: "<h1>Hi</h1>\n\t<p>{~ get title | shout}</p>\n",
+            line_offset: 0,
+            escape_html: true,
+            in_tag: false,
+            strict: false,
+            trim_blocks: false,
+            blocks: None,
+            block: None,
+        };
+        let start = file.source.find("{~").unwrap();
+        let end = file.source.find("}<").unwrap() + 1;
+        let error = file.diagnose(
+            ProcessingError::Compilation {
+                message: "unknown filter: shout".into(),
+            },
+            start..end,
+        );
+        let error = TemplateFile {
+            path: "_parts/page.html",
+            source: "\n\n{~ use \"index.html\"}",
+            line_offset: 2,
+            escape_html: true,
+            in_tag: false,
+            strict: false,
+            trim_blocks: false,
+            blocks: None,
+            block: None,
+        }
+        .include(error, 2..21);
+
+        let ProcessingError::Template { diagnostic } = &error else {
+            panic!("expected a template diagnostic, got {:?}", error);
+        };
+        assert_eq!(
+            TemplateLocation {
+                path: "index.html".into(),
+                line: 2,
+                column: 5,
+            },
+            diagnostic.location
+        );
+        assert_eq!(
+            "index.html:2:5: unknown filter: shout\n  |\n2 | \t<p>{~ get title | shout}</p>\n  | \t   ^^^^^^^^^^^^^^^^^^^^^\n  = included from _parts/page.html:5:1",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn relocates_diagnostics() {
+        let file = TemplateFile {
+            path: "index.html",
+            source: "{~ nope}",
+            line_offset: 0,
+            escape_html: false,
+            in_tag: false,
+            strict: false,
+            trim_blocks: false,
+            blocks: None,
+            block: None,
+        };
+        let mut error = file.include(
+            file.diagnose(
+                ProcessingError::Compilation {
+                    message: "unknown template function: nope".into(),
+                },
+                0..8,
+            ),
+            0..8,
+        );
+        let ProcessingError::Template { diagnostic } = &mut error else {
+            panic!("expected a template diagnostic");
+        };
+
+        diagnostic.relocate("index.html", "_patterns/page.html", 3);
+        assert_eq!("_patterns/page.html", diagnostic.location.path);
+        assert_eq!(4, diagnostic.location.line);
+        assert_eq!("_patterns/page.html", diagnostic.include_stack[0].path);
+        assert_eq!(4, diagnostic.include_stack[0].line);
+    }
+}
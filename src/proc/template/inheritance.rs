@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use codas::types::Text;
+
+use super::TemplateProcessor;
+use super::ast::{Expression, Node, Template};
+use super::diagnostic::TemplateFile;
+use crate::proc::{Environment, LayeredContext, ProcessingError};
+
+/// A template extended by another template via `{~ extends "path" }`.
+#[derive(Debug)]
+pub(super) struct Parent {
+    pub path: Text,
+    pub source: Text,
+    pub line_offset: usize,
+    pub template: Arc<Template>,
+}
+
+impl Parent {
+    /// Returns the file of this template, with
+    /// the same options as the `child` extending it.
+    pub fn file<'a>(&'a self, child: &TemplateFile<'a>) -> TemplateFile<'a> {
+        TemplateFile {
+            path: &self.path,
+            source: &self.source,
+            line_offset: self.line_offset,
+            blocks: None,
+            block: None,
+            ..*child
+        }
+    }
+}
+
+/// Definitions of the `{~ block name }` regions of a
+/// chain of templates extending each other.
+#[derive(Debug, Default)]
+pub(super) struct Blocks<'a> {
+    /// Definitions of each block by name, from the
+    /// most derived template to the root template.
+    definitions: BTreeMap<Text, Vec<Definition<'a>>>,
+}
+
+/// A definition of a block in a template.
+#[derive(Debug)]
+struct Definition<'a> {
+    file: TemplateFile<'a>,
+    nodes: &'a [Node],
+}
+
+impl<'a> Blocks<'a> {
+    /// Collects the blocks defined anywhere in each of `templates`,
+    /// ordered from the most derived template to the root.
+    pub fn collect(templates: impl IntoIterator<Item = (TemplateFile<'a>, &'a Template)>) -> Self {
+        let mut blocks = Self::default();
+        for (file, template) in templates {
+            blocks.collect_nodes(file, &template.nodes);
+        }
+        blocks
+    }
+
+    fn collect_nodes(&mut self, file: TemplateFile<'a>, nodes: &'a [Node]) {
+        for node in nodes {
+            let Node::Expression(Expression {
+                name, args, block, ..
+            }) = node
+            else {
+                continue;
+            };
+
+            // Malformed blocks are reported when they're rendered.
+            if name == "block"
+                && let Some(Ok(name)) = args.first().map(|arg| arg.try_as_identifier())
+                && let Some([branch]) = block.as_deref()
+            {
+                self.definitions.entry(name).or_default().push(Definition {
+                    file,
+                    nodes: &branch.nodes,
+                });
+            }
+
+            for branch in block.iter().flatten() {
+                self.collect_nodes(file, &branch.nodes);
+            }
+        }
+    }
+
+    /// Renders the definition at `index` of the block `name`, appending the
+    /// rendered results to `output`. `{~ super }` expressions inside the
+    /// definition render the definition at `index + 1`.
+    pub fn render(
+        &'a self,
+        env: &Environment,
+        context: &mut LayeredContext,
+        name: &Text,
+        index: usize,
+        output: &mut String,
+    ) -> Result<(), ProcessingError> {
+        let Some((name, definition)) = self
+            .definitions
+            .get_key_value(name)
+            .and_then(|(name, definitions)| Some((name, definitions.get(index)?)))
+        else {
+            return Err(ProcessingError::Compilation {
+                message: format!("no parent definition of block '{}' for 'super'", name).into(),
+            });
+        };
+
+        let file = TemplateFile {
+            blocks: Some(self),
+            block: Some((name, index)),
+            ..definition.file
+        };
+        TemplateProcessor::render_nodes(env, &file, context, definition.nodes, output)
+    }
+}
@@ -0,0 +1,233 @@
+use codas::types::Text;
+
+use super::TemplateProcessor;
+use super::tokenizer::TemplateExpression;
+use crate::proc::{ContextValue, Environment, LayeredContext, ProcessingError};
+
+/// A parameter of a macro, with its default value (if any).
+type Parameter<'a> = (Text, Option<&'a TemplateExpression>);
+
+/// Returns the name of the macro defined by `{~ macro <args> }`.
+pub(super) fn name(args: &[TemplateExpression]) -> Result<Text, ProcessingError> {
+    args.first()
+        .ok_or(ProcessingError::Compilation {
+            message: "missing name in macro expression".into(),
+        })?
+        .try_as_identifier()
+}
+
+/// Binds the arguments `call_args` of a call to the macro defined
+/// by `{~ macro <args> }` into `scope`, a new scope for the macro's body.
+///
+/// Arguments are passed by position or as `name = value`, with values
+/// evaluated in the caller's `context`. Parameters without arguments are
+/// bound to their defaults, which are evaluated in `scope` so that they
+/// may refer to earlier parameters. Values which can't be resolved are
+/// empty, unless `strict` is set:
+///
+/// ```text
+/// {~ macro button(label, href, style = "primary") } ... {~ end }
+/// {~ ui.button "Buy" "/shop" style = "secondary" }
+/// ```
+pub(super) fn bind(
+    env: &Environment,
+    context: &LayeredContext,
+    scope: &mut LayeredContext,
+    args: &[TemplateExpression],
+    call_args: &[TemplateExpression],
+    strict: bool,
+) -> Result<(), ProcessingError> {
+    let name = name(args)?;
+    let parameters = parse_parameters(&args[1..])?;
+
+    // Match each argument with its parameter.
+    let mut values: Vec<Option<&TemplateExpression>> = vec![None; parameters.len()];
+    let mut position = 0;
+    let mut i = 0;
+    while i < call_args.len() {
+        let (index, value) = match &call_args[i..] {
+            [
+                TemplateExpression::Identifier(key),
+                TemplateExpression::Operator(operator),
+                value,
+                ..,
+            ] if operator == "=" => {
+                i += 3;
+                let index = parameters
+                    .iter()
+                    .position(|(parameter, _)| parameter == key)
+                    .ok_or_else(|| ProcessingError::Compilation {
+                        message: format!("unknown argument '{}' to macro {}", key, name).into(),
+                    })?;
+                (index, value)
+            }
+            [value, ..] => {
+                i += 1;
+                position += 1;
+                if position > parameters.len() {
+                    return Err(ProcessingError::Compilation {
+                        message: format!("too many arguments to macro {}", name).into(),
+                    });
+                }
+                (position - 1, value)
+            }
+            [] => unreachable!(),
+        };
+        if values[index].replace(value).is_some() {
+            return Err(ProcessingError::Compilation {
+                message: format!(
+                    "duplicate argument '{}' to macro {}",
+                    parameters[index].0, name
+                )
+                .into(),
+            });
+        }
+    }
+
+    for ((parameter, default), value) in parameters.into_iter().zip(values) {
+        let value = match (value, default) {
+            (Some(value), _) => evaluate(env, context, value, strict)?,
+            (None, Some(default)) => evaluate(env, scope, default, strict)?,
+            (None, None) => {
+                return Err(ProcessingError::Compilation {
+                    message: format!("missing argument '{}' to macro {}", parameter, name).into(),
+                });
+            }
+        };
+        scope.insert(parameter, value);
+    }
+
+    Ok(())
+}
+
+/// Parses the parameters following a macro's name, which
+/// may be wrapped in parentheses: `(a, b, c = "default")`.
+fn parse_parameters(args: &[TemplateExpression]) -> Result<Vec<Parameter<'_>>, ProcessingError> {
+    let args = match args {
+        [
+            TemplateExpression::Operator(open),
+            args @ ..,
+            TemplateExpression::Operator(close),
+        ] if open == "(" && close == ")" => args,
+        args => args,
+    };
+
+    let mut parameters: Vec<Parameter> = vec![];
+    let mut i = 0;
+    while i < args.len() {
+        let parameter = args[i].try_as_identifier()?;
+        TemplateProcessor::check_variable_name(&parameter)?;
+        if parameters
+            .iter()
+            .any(|(existing, _)| *existing == parameter)
+        {
+            return Err(ProcessingError::Compilation {
+                message: format!("duplicate macro parameter: {}", parameter).into(),
+            });
+        }
+
+        match &args[i + 1..] {
+            [TemplateExpression::Operator(operator), default, ..] if operator == "=" => {
+                parameters.push((parameter, Some(default)));
+                i += 3;
+            }
+            _ => {
+                parameters.push((parameter, None));
+                i += 1;
+            }
+        }
+    }
+
+    Ok(parameters)
+}
+
+/// Evaluates a macro argument, which is empty if
+/// it's a missing variable (unless `strict` is set).
+fn evaluate(
+    env: &Environment,
+    context: &LayeredContext,
+    value: &TemplateExpression,
+    strict: bool,
+) -> Result<ContextValue, ProcessingError> {
+    match TemplateProcessor::evaluate_value(env, context, value)? {
+        Some(value) => Ok(value),
+        None if strict => {
+            let identifier = match value {
+                TemplateExpression::Filtered { value, .. } => value,
+                value => value,
+            };
+            Err(TemplateProcessor::undefined_variable(
+                &identifier.try_as_identifier()?,
+            ))
+        }
+        None => Ok(ContextValue::Text("".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proc::Context;
+
+    #[test]
+    fn binds_arguments() {
+        let args = [
+            TemplateExpression::Identifier("button".into()),
+            TemplateExpression::Operator("(".into()),
+            TemplateExpression::Identifier("label".into()),
+            TemplateExpression::Identifier("style".into()),
+            TemplateExpression::Operator("=".into()),
+            TemplateExpression::Identifier("label".into()),
+            TemplateExpression::Operator(")".into()),
+        ];
+        let context = LayeredContext::from_flat(Context::new());
+
+        let mut scope = context.child_scope();
+        bind(
+            &Environment::test(),
+            &context,
+            &mut scope,
+            &args,
+            &[TemplateExpression::String("Buy".into())],
+            false,
+        )
+        .unwrap();
+        assert!(matches!(
+            scope.get(&"style".into()),
+            Some(ContextValue::Text(style)) if style == "Buy"
+        ));
+
+        let mut scope = context.child_scope();
+        bind(
+            &Environment::test(),
+            &context,
+            &mut scope,
+            &args,
+            &[
+                TemplateExpression::Identifier("style".into()),
+                TemplateExpression::Operator("=".into()),
+                TemplateExpression::String("primary".into()),
+                TemplateExpression::String("Buy".into()),
+            ],
+            false,
+        )
+        .unwrap();
+        assert!(matches!(
+            scope.get(&"style".into()),
+            Some(ContextValue::Text(style)) if style == "primary"
+        ));
+
+        let error = bind(
+            &Environment::test(),
+            &context,
+            &mut scope,
+            &args,
+            &[],
+            false,
+        );
+        assert_eq!(
+            "missing argument 'label' to macro button",
+            error.unwrap_err().to_string()
+        );
+    }
+}
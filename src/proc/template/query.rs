@@ -0,0 +1,426 @@
+use std::cmp::Ordering;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use codas::types::Text;
+
+use super::TemplateProcessor;
+use super::tokenizer::TemplateExpression;
+use crate::proc::{Context, ContextValue, Environment, LayeredContext, ProcessingError};
+use crate::tool::procs::ASSET_PATH_CONTEXT_KEY_PREFIX;
+
+/// Clauses filtering, ordering, and slicing the items of a `for` loop:
+///
+/// ```text
+/// [where key is [not] value]... [where key contains value]...
+/// [sort key [asc|desc]] [offset N] [limit N] [group by key [year|month]]
+/// ```
+///
+/// Clauses may be written in any order, but are always applied
+/// in the order `where`, `sort`, `offset`, `limit`, then `group`.
+#[derive(Debug, Default)]
+pub(super) struct LoopQuery {
+    conditions: Vec<Condition>,
+    sort: Option<(Text, bool)>,
+    offset: usize,
+    limit: Option<usize>,
+    group: Option<Grouping>,
+}
+
+/// A `group by` clause of a [LoopQuery].
+#[derive(Debug)]
+struct Grouping {
+    key: Text,
+
+    /// The period to group dates by, if
+    /// the key's values should be dates.
+    period: Option<Period>,
+}
+
+/// Periods of time which dates can be grouped by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Period {
+    Year,
+    Month,
+}
+
+/// A `where` clause of a [LoopQuery].
+#[derive(Debug)]
+struct Condition {
+    key: Text,
+    operator: Operator,
+    value: TemplateExpression,
+}
+
+/// Comparison operators supported in `where` clauses.
+#[derive(Debug, PartialEq, Eq)]
+enum Operator {
+    /// The item's field equals the value.
+    Is,
+
+    /// The item's field does not equal the value.
+    IsNot,
+
+    /// The item's field is a list containing the value,
+    /// or text containing the value.
+    Contains,
+}
+
+impl LoopQuery {
+    /// Parses a query from the clauses in `args`.
+    pub fn parse(args: &[TemplateExpression]) -> Result<Self, ProcessingError> {
+        let mut query = Self::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let clause = arg.try_as_identifier()?;
+            match clause.as_str() {
+                "where" => {
+                    let key = Self::next_arg(&mut args, &clause)?.try_as_identifier()?;
+                    let mut operator = match Self::next_arg(&mut args, &clause)?
+                        .try_as_identifier()?
+                        .as_str()
+                    {
+                        "is" => Operator::Is,
+                        "contains" => Operator::Contains,
+                        other => {
+                            return Err(ProcessingError::Compilation {
+                                message: format!(
+                                    "expected 'is' or 'contains' in where clause, got '{}'",
+                                    other
+                                )
+                                .into(),
+                            });
+                        }
+                    };
+
+                    let mut value = Self::next_arg(&mut args, &clause)?;
+                    if operator == Operator::Is
+                        && value.try_as_identifier().is_ok_and(|id| id == "not")
+                    {
+                        operator = Operator::IsNot;
+                        value = Self::next_arg(&mut args, &clause)?;
+                    }
+
+                    query.conditions.push(Condition {
+                        key,
+                        operator,
+                        value: value.clone(),
+                    });
+                }
+
+                "sort" => {
+                    let key = Self::next_arg(&mut args, &clause)?.try_as_identifier()?;
+
+                    // The sort direction is optional, defaulting to ascending.
+                    let direction = args.as_slice().first().and_then(|a| {
+                        a.try_as_identifier()
+                            .ok()
+                            .filter(|id| id == "asc" || id == "desc")
+                    });
+                    if direction.is_some() {
+                        args.next();
+                    }
+
+                    query.sort = Some((key, direction.is_some_and(|d| d == "desc")));
+                }
+
+                "offset" => {
+                    query.offset = Self::parse_count(Self::next_arg(&mut args, &clause)?)?;
+                }
+
+                "limit" => {
+                    query.limit = Some(Self::parse_count(Self::next_arg(&mut args, &clause)?)?);
+                }
+
+                "group" => {
+                    let by = Self::next_arg(&mut args, &clause)?.try_as_identifier()?;
+                    if by != "by" {
+                        return Err(ProcessingError::Compilation {
+                            message: format!("expected 'by' in group clause, got '{}'", by).into(),
+                        });
+                    }
+                    let key = Self::next_arg(&mut args, &clause)?.try_as_identifier()?;
+
+                    // The period is optional, grouping by the key's exact values.
+                    let period = args.as_slice().first().and_then(|a| {
+                        match a.try_as_identifier().ok()?.as_str() {
+                            "year" => Some(Period::Year),
+                            "month" => Some(Period::Month),
+                            _ => None,
+                        }
+                    });
+                    if period.is_some() {
+                        args.next();
+                    }
+
+                    query.group = Some(Grouping { key, period });
+                }
+
+                other => {
+                    return Err(ProcessingError::Compilation {
+                        message: format!("unknown clause in for loop: {}", other).into(),
+                    });
+                }
+            }
+        }
+
+        Ok(query)
+    }
+
+    /// Applies this query to `items`, returning the matching items.
+    pub fn apply(
+        &self,
+        env: &Environment,
+        context: &LayeredContext,
+        items: Vec<ContextValue>,
+    ) -> Result<Vec<ContextValue>, ProcessingError> {
+        // Resolve each condition's value once, up front.
+        let mut conditions = Vec::with_capacity(self.conditions.len());
+        for condition in &self.conditions {
+            let value = TemplateProcessor::evaluate_value(env, context, &condition.value)?;
+            let text = value
+                .as_ref()
+                .and_then(|value| TemplateProcessor::render_value(env, value));
+            conditions.push((condition, value, text));
+        }
+
+        let mut items: Vec<ContextValue> = items
+            .into_iter()
+            .filter(|item| {
+                conditions.iter().all(|(condition, value, text)| {
+                    let field = Self::extract_field(item, &condition.key);
+                    let matches = match (&condition.operator, field, text) {
+                        (_, _, None) => false,
+                        (Operator::Is | Operator::IsNot, Some(field), Some(_)) => {
+                            value.as_ref().is_some_and(|value| {
+                                TemplateProcessor::values_equal(env, field, value)
+                            })
+                        }
+                        (Operator::Contains, Some(ContextValue::List(list)), Some(value)) => {
+                            list.iter().any(|entry| {
+                                TemplateProcessor::render_value(env, entry).as_ref() == Some(value)
+                            })
+                        }
+                        (Operator::Contains, Some(ContextValue::Text(text)), Some(value)) => {
+                            text.contains(value.as_str())
+                        }
+                        _ => false,
+                    };
+
+                    if condition.operator == Operator::IsNot {
+                        !matches
+                    } else {
+                        matches
+                    }
+                })
+            })
+            .collect();
+
+        if let Some((sort_key, descending)) = &self.sort {
+            // Schwartzian transform: parse sort keys
+            // once rather than on every comparison.
+            let mut keyed: Vec<_> = items
+                .into_iter()
+                .map(|item| {
+                    (
+                        SortKey::new(env, Self::extract_field(&item, sort_key)),
+                        item,
+                    )
+                })
+                .collect();
+            keyed.sort_by(|(a, _), (b, _)| {
+                let cmp = a.cmp(b);
+                if *descending { cmp.reverse() } else { cmp }
+            });
+            items = keyed.into_iter().map(|(_, item)| item).collect();
+        }
+
+        let items = items.into_iter().skip(self.offset);
+        let items = match self.limit {
+            Some(limit) => items.take(limit).collect(),
+            None => items.collect(),
+        };
+
+        Ok(match &self.group {
+            Some(grouping) => grouping.apply(env, items),
+            None => items,
+        })
+    }
+
+    /// Extracts a possibly-dotted field from a table item.
+    fn extract_field<'a>(item: &'a ContextValue, key: &str) -> Option<&'a ContextValue> {
+        let mut current = item;
+        for segment in key.split('.') {
+            match current {
+                ContextValue::Table(table) => {
+                    let segment: Text = segment.into();
+                    current = table.get(&segment)?;
+                }
+                _ => return None,
+            }
+        }
+        Some(current)
+    }
+
+    /// Returns the next argument of a `clause`, failing if there isn't one.
+    fn next_arg<'a>(
+        args: &mut std::slice::Iter<'a, TemplateExpression>,
+        clause: &str,
+    ) -> Result<&'a TemplateExpression, ProcessingError> {
+        args.next().ok_or_else(|| ProcessingError::Compilation {
+            message: format!("incomplete '{}' clause in for loop", clause).into(),
+        })
+    }
+
+    /// Parses a non-negative count for an `offset` or `limit` clause.
+    fn parse_count(arg: &TemplateExpression) -> Result<usize, ProcessingError> {
+        let count = match arg {
+            TemplateExpression::Number(n) | TemplateExpression::String(n) => n.parse().ok(),
+            _ => None,
+        };
+        count.ok_or_else(|| ProcessingError::Compilation {
+            message: format!("expected a non-negative count; got {:?}", arg).into(),
+        })
+    }
+}
+
+/// The value of an item's field that items are sorted by: dates sort
+/// chronologically before numbers, which sort numerically before all
+/// other values, which sort by their text (missing values first).
+#[derive(Debug)]
+enum SortKey {
+    Date(NaiveDateTime),
+    Number(f64),
+    Text(Option<Text>),
+}
+
+impl SortKey {
+    fn new(env: &Environment, value: Option<&ContextValue>) -> Self {
+        let Some(value) = value else {
+            return Self::Text(None);
+        };
+        if let Some(date) = TemplateProcessor::value_date(value) {
+            Self::Date(date)
+        } else if let Some(number) = TemplateProcessor::value_number(value) {
+            Self::Number(number)
+        } else {
+            Self::Text(TemplateProcessor::render_value(env, value))
+        }
+    }
+
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Date(a), Self::Date(b)) => a.cmp(b),
+            (Self::Number(a), Self::Number(b)) => a.total_cmp(b),
+            (Self::Text(a), Self::Text(b)) => a.cmp(b),
+            (a, b) => a.rank().cmp(&b.rank()),
+        }
+    }
+
+    fn rank(&self) -> usize {
+        match self {
+            Self::Date(_) => 0,
+            Self::Number(_) => 1,
+            Self::Text(_) => 2,
+        }
+    }
+}
+
+impl Grouping {
+    /// Groups `items` by their values of [Self::key], returning a table
+    /// of each group's `key` and `items`, in the order each group's first
+    /// item appears. Items with a list of values (like `tags`) are put
+    /// into the group of each value, and items without a value are skipped.
+    ///
+    /// If [Self::period] is set, values are grouped by the year (`2025`)
+    /// or month (`2025-04`) of their date, and each group's table also
+    /// contains the `date` its period starts on (`2025-04-01`).
+    fn apply(&self, env: &Environment, items: Vec<ContextValue>) -> Vec<ContextValue> {
+        let mut groups: Vec<(Text, Option<NaiveDate>, Vec<ContextValue>)> = vec![];
+
+        for item in items {
+            let values = match LoopQuery::extract_field(&item, &self.key) {
+                Some(ContextValue::List(values)) => values.iter().collect(),
+                Some(value) => vec![value],
+                None => vec![],
+            };
+
+            let mut keys = vec![];
+            for value in values {
+                let key = match self.period {
+                    None => {
+                        let Some(value) = TemplateProcessor::render_value(env, value) else {
+                            continue;
+                        };
+                        (value, None)
+                    }
+                    Some(period) => {
+                        let Some(date) = TemplateProcessor::value_date(value) else {
+                            continue;
+                        };
+                        let date = date.date();
+                        let (key, start) = match period {
+                            Period::Year => (format!("{:04}", date.year()), date.with_ordinal(1)),
+                            Period::Month => (date.format("%Y-%m").to_string(), date.with_day(1)),
+                        };
+                        (key.into(), start)
+                    }
+                };
+
+                // Items are only added to each group once.
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+
+            for (key, date) in keys {
+                match groups.iter_mut().find(|(existing, ..)| *existing == key) {
+                    Some((.., items)) => items.push(item.clone()),
+                    None => groups.push((key, date, vec![item.clone()])),
+                }
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|(key, date, items)| {
+                let mut group = Context::new();
+                group.insert("key".into(), ContextValue::Text(key));
+                group.insert("items".into(), ContextValue::List(items));
+                if let Some(date) = date {
+                    let date = toml::value::Date {
+                        year: date.year() as u16,
+                        month: date.month() as u8,
+                        day: date.day() as u8,
+                    };
+                    let date = toml::value::Datetime {
+                        date: Some(date),
+                        time: None,
+                        offset: None,
+                    };
+                    group.insert("date".into(), ContextValue::Date(date));
+                }
+                ContextValue::Table(group)
+            })
+            .collect()
+    }
+}
+
+/// Collects the metadata of all assets in `dir_path` and its
+/// subdirectories (e.g., "logs" also gathers from "logs/ldjam-57",
+/// "logs/guide-to-ai", etc.).
+pub(super) fn collect_assets(context: &LayeredContext, dir_path: &str) -> Vec<ContextValue> {
+    let assets_key: Text = format!("{}{}", ASSET_PATH_CONTEXT_KEY_PREFIX, dir_path).into();
+    let prefix: Text = format!("{}{}/", ASSET_PATH_CONTEXT_KEY_PREFIX, dir_path).into();
+    let mut all_items: Vec<ContextValue> = Vec::new();
+
+    for (key, value) in context.iter_by_prefix(ASSET_PATH_CONTEXT_KEY_PREFIX) {
+        if (*key == assets_key || key.starts_with(prefix.as_str()))
+            && let ContextValue::List(items) = value
+        {
+            all_items.extend(items.iter().cloned());
+        }
+    }
+
+    all_items
+}
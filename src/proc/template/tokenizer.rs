@@ -6,9 +6,15 @@ use crate::proc::ProcessingError;
 /// Tokenizer for text assets containing template expressions.
 #[derive(Logos, Debug, PartialEq, Eq, Clone)]
 pub enum Token {
-    /// Opening brace of a template expression.
+    /// Opening brace of a template expression,
+    /// optionally with a whitespace trim marker.
     #[token(r#"{~"#, parse_template_expression)]
+    #[token(r#"{~-"#, parse_template_expression)]
     OpenTemplate(Result<TemplateExpression, String>),
+
+    /// A comment, which extends to its matching closing brace.
+    #[token(r#"{~#"#, skip_comment)]
+    Comment(Result<(), String>),
 }
 
 /// Tokenizer for an individual template expression.
@@ -25,12 +31,27 @@ enum TemplateToken {
     #[regex(r#""([^"\\]|\\.)*""#)]
     String,
 
+    /// A numeric literal, optionally signed and with a fractional part.
+    #[regex(r"-?[0-9]+(\.[0-9]+)?")]
+    Number,
+
+    /// A filter separator, passing the preceding value into a filter.
+    #[token("|")]
+    Pipe,
+
+    /// An operator, like the `=` in an assignment,
+    /// a comparison, or a parenthesis.
+    #[regex(r"<=|>=|[=<>()]")]
+    Operator,
+
     /// An argument separator.
     #[token(",")]
     Separator,
 
-    /// Closing brace of a template expression.
+    /// Closing brace of a template expression,
+    /// optionally with a whitespace trim marker.
     #[token(r#"}"#)]
+    #[token(r#"-}"#)]
     CloseTemplate,
 }
 
@@ -44,6 +65,20 @@ pub enum TemplateExpression {
     /// A literal string.
     String(Text),
 
+    /// A literal number, stored as written.
+    Number(Text),
+
+    /// An operator, like the `=` in an assignment,
+    /// a comparison, or a parenthesis.
+    Operator(Text),
+
+    /// A value passed through a pipeline of filters:
+    /// `value | filter arg | filter`.
+    Filtered {
+        value: Box<TemplateExpression>,
+        filters: Vec<Filter>,
+    },
+
     /// A function call with arguments.
     Function {
         name: Text,
@@ -51,6 +86,15 @@ pub enum TemplateExpression {
     },
 }
 
+/// A filter applied to a value in a [TemplateExpression::Filtered] pipeline.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Filter {
+    pub name: Text,
+
+    /// Literal arguments passed to the filter.
+    pub args: Vec<TemplateExpression>,
+}
+
 impl TemplateExpression {
     pub fn try_as_identifier(&self) -> Result<Text, ProcessingError> {
         match self {
@@ -71,6 +115,49 @@ impl TemplateExpression {
     }
 }
 
+/// Removes the surrounding quotes from a string literal and unescapes it.
+fn unescape(slice: &str) -> String {
+    slice[1..slice.len() - 1]
+        .replace(r#"\\\\"#, r#"\\"#) // handle escaped backslash first (for double escaping)
+        .replace(r#"\\"#, r#"\ "#) // handle single escaped backslash
+        .replace(r#"\""#, r#"""#)
+        .replace(r#"\n"#, "\n")
+        .replace(r#"\t"#, "\t")
+        .replace(r#"\ "#, r#"\\"#) // restore single backslash
+}
+
+/// Skips the body of a comment, up to and including its closing brace.
+///
+/// Braces inside the comment are balanced, so that the closing braces of
+/// expressions (like `{~# {~ get x } }`) or of code (like `{~# a { b } }`)
+/// inside it don't close the comment.
+fn skip_comment(lexer: &mut Lexer<Token>) -> Result<(), String> {
+    let remainder = lexer.remainder();
+    let mut depth = 1;
+    let mut in_string = false;
+    let mut chars = remainder.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            // Strings inside nested expressions may contain braces.
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' if depth > 1 => in_string = !in_string,
+            _ if in_string => {}
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    lexer.bump(i + 1);
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+    }
+    Err("unclosed comment".to_string())
+}
+
 /// Parses a series of [TemplateToken]s into a [TemplateExpression].
 fn parse_template_expression(lexer: &mut Lexer<Token>) -> Result<TemplateExpression, String> {
     let mut template_lexer = lexer.clone().morph::<TemplateToken>();
@@ -81,32 +168,87 @@ fn parse_template_expression(lexer: &mut Lexer<Token>) -> Result<TemplateExpress
         let identifier = template_lexer.slice();
 
         // The following tokens up to the end of the template must be arguments.
+        // Arguments followed by a `|` are wrapped into a filter pipeline, with
+        // any literals following a filter's name becoming that filter's arguments.
         let mut args = vec![];
+        let mut expecting_filter = false;
+        let mut in_filter = false;
+        let mut error = None;
         while let Some(Ok(token)) = template_lexer.next() {
-            match token {
+            let arg = match token {
                 TemplateToken::Identifier => {
-                    args.push(TemplateExpression::Identifier(
-                        template_lexer.slice().into(),
-                    ));
+                    TemplateExpression::Identifier(template_lexer.slice().into())
                 }
                 TemplateToken::String => {
-                    let slice = template_lexer.slice();
-                    // Remove the surrounding quotes and unescape.
-                    let unescaped = slice[1..slice.len() - 1]
-                        .replace(r#"\\\\"#, r#"\\"#) // handle escaped backslash first (for double escaping)
-                        .replace(r#"\\"#, r#"\ "#) // handle single escaped backslash
-                        .replace(r#"\""#, r#"""#)
-                        .replace(r#"\n"#, "\n")
-                        .replace(r#"\t"#, "\t")
-                        .replace(r#"\ "#, r#"\\"#); // restore single backslash
-                    args.push(TemplateExpression::String(unescaped.into()));
+                    TemplateExpression::String(unescape(template_lexer.slice()).into())
+                }
+                TemplateToken::Number => TemplateExpression::Number(template_lexer.slice().into()),
+                TemplateToken::Operator => {
+                    TemplateExpression::Operator(template_lexer.slice().into())
                 }
-                TemplateToken::Separator => {}
+                TemplateToken::Pipe => {
+                    match args.pop() {
+                        Some(filtered @ TemplateExpression::Filtered { .. }) => args.push(filtered),
+                        Some(value) => args.push(TemplateExpression::Filtered {
+                            value: Box::new(value),
+                            filters: vec![],
+                        }),
+                        None => {
+                            error.get_or_insert_with(|| {
+                                "expected a value before '|' in template expression".to_string()
+                            });
+                        }
+                    }
+                    expecting_filter = true;
+                    continue;
+                }
+                TemplateToken::Separator => continue,
                 TemplateToken::CloseTemplate => {
                     *lexer = template_lexer.morph();
                     break;
                 }
+            };
+
+            // The first token after a `|` names the filter.
+            if expecting_filter {
+                expecting_filter = false;
+                in_filter = true;
+                match (arg, args.last_mut()) {
+                    (
+                        TemplateExpression::Identifier(name),
+                        Some(TemplateExpression::Filtered { filters, .. }),
+                    ) => filters.push(Filter { name, args: vec![] }),
+                    (arg, _) => {
+                        error.get_or_insert_with(|| {
+                            format!("expected filter name after '|', got: {:?}", arg)
+                        });
+                    }
+                }
+                continue;
             }
+
+            // Literals directly following a filter's name are its arguments.
+            if in_filter
+                && matches!(
+                    arg,
+                    TemplateExpression::String(_) | TemplateExpression::Number(_)
+                )
+                && let Some(TemplateExpression::Filtered { filters, .. }) = args.last_mut()
+                && let Some(filter) = filters.last_mut()
+            {
+                filter.args.push(arg);
+                continue;
+            }
+
+            in_filter = false;
+            args.push(arg);
+        }
+
+        if expecting_filter {
+            error.get_or_insert_with(|| "missing filter name after '|'".to_string());
+        }
+        if let Some(error) = error {
+            return Err(error);
         }
 
         Ok(TemplateExpression::Function {
@@ -204,4 +346,145 @@ mod tests {
         );
         assert_eq!(lexer.next(), None);
     }
+
+    #[test]
+    fn lexes_assignments() {
+        let mut lexer = Token::lexer(r#"{~ set heading = title | upper }"#);
+        assert_eq!(
+            lexer.next(),
+            Some(Ok(Token::OpenTemplate(Ok(TemplateExpression::Function {
+                name: "set".into(),
+                args: vec![
+                    TemplateExpression::Identifier("heading".into()),
+                    TemplateExpression::Operator("=".into()),
+                    TemplateExpression::Filtered {
+                        value: Box::new(TemplateExpression::Identifier("title".into())),
+                        filters: vec![Filter {
+                            name: "upper".into(),
+                            args: vec![],
+                        }],
+                    },
+                ],
+            }))))
+        );
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn lexes_trim_markers() {
+        let mut lexer = Token::lexer(r#"{~- get name -} {~-get name-}"#);
+        let expected = Some(Ok(Token::OpenTemplate(Ok(TemplateExpression::Function {
+            name: "get".into(),
+            args: vec![TemplateExpression::Identifier("name".into())],
+        }))));
+        assert_eq!(lexer.next(), expected);
+        assert_eq!(lexer.next(), Some(Err(())));
+        assert_eq!(lexer.next(), expected);
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn lexes_comments() {
+        let mut lexer = Token::lexer("a{~# a }b\n {~ get x }");
+        assert_eq!(lexer.next(), Some(Err(())));
+        assert_eq!(lexer.next(), Some(Ok(Token::Comment(Ok(())))));
+        assert_eq!(lexer.slice(), "{~# a }");
+        assert_eq!(lexer.next(), Some(Err(())));
+
+        // Expressions inside comments are commented out with them.
+        let mut lexer = Token::lexer(r#"{~# note {~ get x | default "}" } still comment? }b"#);
+        assert_eq!(lexer.next(), Some(Ok(Token::Comment(Ok(())))));
+        assert_eq!(
+            lexer.slice(),
+            r#"{~# note {~ get x | default "}" } still comment? }"#
+        );
+        assert_eq!(lexer.next(), Some(Err(())));
+
+        // So are braces of code inside comments.
+        let mut lexer = Token::lexer("{~# .a { color: red } }b");
+        assert_eq!(lexer.next(), Some(Ok(Token::Comment(Ok(())))));
+        assert_eq!(lexer.slice(), "{~# .a { color: red } }");
+
+        let mut lexer = Token::lexer("{~# {~ if a }{~ end } }");
+        assert_eq!(lexer.next(), Some(Ok(Token::Comment(Ok(())))));
+        assert_eq!(lexer.next(), None);
+
+        let mut lexer = Token::lexer("{~# unclosed");
+        assert_eq!(
+            lexer.next(),
+            Some(Ok(Token::Comment(Err("unclosed comment".to_string()))))
+        );
+    }
+
+    #[test]
+    fn lexes_filters() {
+        let mut lexer = Token::lexer(r#"{~ get title | truncate 60 | replace "a" "b" | upper }"#);
+        assert_eq!(
+            lexer.next(),
+            Some(Ok(Token::OpenTemplate(Ok(TemplateExpression::Function {
+                name: "get".into(),
+                args: vec![TemplateExpression::Filtered {
+                    value: Box::new(TemplateExpression::Identifier("title".into())),
+                    filters: vec![
+                        Filter {
+                            name: "truncate".into(),
+                            args: vec![TemplateExpression::Number("60".into())],
+                        },
+                        Filter {
+                            name: "replace".into(),
+                            args: vec![
+                                TemplateExpression::String("a".into()),
+                                TemplateExpression::String("b".into()),
+                            ],
+                        },
+                        Filter {
+                            name: "upper".into(),
+                            args: vec![],
+                        },
+                    ],
+                }],
+            }))))
+        );
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn lexes_filters_between_arguments() {
+        let mut lexer = Token::lexer(r#"{~ use "_part.html", with title | upper as label }"#);
+        assert_eq!(
+            lexer.next(),
+            Some(Ok(Token::OpenTemplate(Ok(TemplateExpression::Function {
+                name: "use".into(),
+                args: vec![
+                    TemplateExpression::String("_part.html".into()),
+                    TemplateExpression::Identifier("with".into()),
+                    TemplateExpression::Filtered {
+                        value: Box::new(TemplateExpression::Identifier("title".into())),
+                        filters: vec![Filter {
+                            name: "upper".into(),
+                            args: vec![],
+                        }],
+                    },
+                    TemplateExpression::Identifier("as".into()),
+                    TemplateExpression::Identifier("label".into()),
+                ],
+            }))))
+        );
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[test]
+    fn rejects_filters_without_names() {
+        let mut lexer = Token::lexer(r#"{~ get title | }"#);
+        assert!(matches!(
+            lexer.next(),
+            Some(Ok(Token::OpenTemplate(Err(_))))
+        ));
+
+        let mut lexer = Token::lexer(r#"{~ get title | "upper" }"#);
+        assert!(matches!(
+            lexer.next(),
+            Some(Ok(Token::OpenTemplate(Err(_))))
+        ));
+    }
 }
@@ -1,6 +1,7 @@
 //! This module contains implementations for the interactive tools.
 
 mod color;
+pub mod data;
 pub mod kits;
 pub mod opengraph;
 pub mod palette;
@@ -15,7 +16,7 @@ use serde::Deserialize;
 use tokio::fs;
 
 use crate::tool::opengraph::OpenGraphConfig;
-use crate::tool::procs::ProcessorConfig;
+use crate::tool::procs::{ProcessorConfig, TaxonomyConfig};
 
 /// Default configuration profile.
 const DEFAULT_CONFIG_PROFILE: &str = "default";
@@ -51,6 +52,11 @@ favicon = {}
 [default.tools]
 opengraph = {}
 
+# Generates a page per distinct value of a frontmatter field,
+# wrapped in a pattern, plus an index page of all values.
+# [default.taxonomies]
+# tags = { pattern = "_tag.html", path = "tags/{slug}" }
+
 [production.procs]
 canonicalize = { root = "https://www.example.com/" }
 "#;
@@ -93,6 +99,8 @@ pub struct ConfigProfile {
     context: toml::Table,
     #[serde(default)]
     paths: PathsConfig,
+    #[serde(default)]
+    taxonomies: BTreeMap<String, TaxonomyConfig>,
 }
 
 /// Path configuration in a [ConfigProfile].
@@ -136,6 +144,11 @@ impl ConfigProfile {
             merged.tools.0.insert(key.clone(), value.clone());
         }
 
+        // Merge taxonomies
+        for (key, value) in &other.taxonomies {
+            merged.taxonomies.insert(key.clone(), value.clone());
+        }
+
         merged
     }
 }
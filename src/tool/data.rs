@@ -0,0 +1,243 @@
+//! Data files load site-wide data (like navigation menus or team members)
+//! from `_data/` directories into the asset processing context.
+//!
+//! Each TOML, JSON, or CSV file in a `_data/` directory is stored under
+//! the `data` context key by its path: `_data/nav.toml` as `data.nav`, and
+//! `_data/blog/authors.json` as `data.blog.authors`. Data files in a kit's
+//! `_data/` directory are stored beneath the kit's name, as `data.<kit>.nav`.
+
+use crate::proc::{Context, ContextValue, ProcessingError, context_from_toml};
+
+/// Directory containing data files, relative to a source directory.
+const DATA_DIR: &str = "_data";
+
+/// Key storing the table of all loaded data in the processing context.
+pub const DATA_CONTEXT_KEY: &str = "data";
+
+/// Returns the keys a data file at `path` should be stored under, relative
+/// to the [DATA_CONTEXT_KEY] table, or `None` if `path` isn't a data file.
+pub fn data_keys(path: &str) -> Option<Vec<&str>> {
+    let mut components = path.split(['/', '\\']);
+    if components.next()? != DATA_DIR {
+        return None;
+    }
+
+    let mut keys: Vec<&str> = components.collect();
+    let (stem, extension) = keys.pop()?.rsplit_once('.')?;
+    if !matches!(extension, "toml" | "json" | "csv") || stem.is_empty() {
+        return None;
+    }
+    keys.push(stem);
+    Some(keys)
+}
+
+/// Parses the `content` of the data file at `path` by its extension.
+///
+/// TOML files are parsed into tables, JSON files into the values they
+/// contain, and CSV files into a list of tables (one per row) keyed by
+/// the file's header row. All CSV fields are stored as text.
+pub fn parse(path: &str, content: &[u8]) -> Result<ContextValue, ProcessingError> {
+    let text = std::str::from_utf8(content).map_err(|_| ProcessingError::NonTextual)?;
+    let malformed = |e: &dyn std::fmt::Display| ProcessingError::Malformed {
+        message: format!("invalid data in {}: {}", path, e).into(),
+    };
+
+    match path.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("toml") => {
+            let table: toml::Table = toml::from_str(text).map_err(|e| malformed(&e))?;
+            Ok(ContextValue::Table(context_from_toml(table)?))
+        }
+        Some("json") => {
+            let value: serde_json::Value = serde_json::from_str(text).map_err(|e| malformed(&e))?;
+            Ok(from_json(value))
+        }
+        Some("csv") => parse_csv(text).map_err(|e| malformed(&e)),
+        _ => Err(malformed(&"unsupported data file type")),
+    }
+}
+
+/// Inserts `value` into the [DATA_CONTEXT_KEY] table of `context`
+/// beneath `keys`, creating any tables along the way.
+pub fn insert(context: &mut Context, keys: &[&str], value: ContextValue) {
+    let Some((last, parents)) = keys.split_last() else {
+        return;
+    };
+
+    let mut table = context;
+    for key in std::iter::once(&DATA_CONTEXT_KEY).chain(parents) {
+        let entry = table
+            .entry((*key).into())
+            .or_insert_with(|| ContextValue::Table(Context::default()));
+        if !matches!(entry, ContextValue::Table(_)) {
+            tracing::warn!("Replacing non-table data at `{}` with a table", key);
+            *entry = ContextValue::Table(Context::default());
+        }
+        let ContextValue::Table(next) = entry else {
+            unreachable!()
+        };
+        table = next;
+    }
+
+    if table.insert((*last).into(), value).is_some() {
+        tracing::warn!("Data file `{}` replaces existing data", keys.join("."));
+    }
+}
+
+/// Converts a JSON [serde_json::Value] into a [ContextValue].
+fn from_json(value: serde_json::Value) -> ContextValue {
+    match value {
+        serde_json::Value::Null => ContextValue::Text("".into()),
+        serde_json::Value::Bool(b) => ContextValue::Bool(b),
+        // Integers too large for an `i64` remain text, so they're exact.
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(n), _) => ContextValue::Integer(n),
+            (None, Some(float)) if n.is_f64() => ContextValue::Number(float),
+            _ => ContextValue::Text(n.to_string().into()),
+        },
+        serde_json::Value::String(s) => ContextValue::Text(s.into()),
+        serde_json::Value::Array(items) => {
+            ContextValue::List(items.into_iter().map(from_json).collect())
+        }
+        serde_json::Value::Object(object) => ContextValue::Table(
+            object
+                .into_iter()
+                .map(|(key, value)| (key.into(), from_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Parses CSV `text` into a list of tables, one per row,
+/// keyed by the values of the header (first) row.
+///
+/// Fields may be quoted with `"`, in which case they may contain
+/// commas, newlines, and quotes escaped as `""`. Rows with fewer
+/// fields than the header omit the missing keys.
+fn parse_csv(text: &str) -> Result<ContextValue, &'static str> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        match (quoted, char) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, char) => field.push(char),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, char) => field.push(char),
+        }
+    }
+    if quoted {
+        return Err("unterminated quoted field");
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    // Skip blank lines.
+    let mut rows = rows
+        .into_iter()
+        .filter(|row| !matches!(row.as_slice(), [field] if field.is_empty()));
+    let Some(header) = rows.next() else {
+        return Ok(ContextValue::List(vec![]));
+    };
+
+    let items = rows
+        .map(|row| {
+            let table = header
+                .iter()
+                .zip(row)
+                .map(|(key, value)| (key.trim().into(), ContextValue::Text(value.into())))
+                .collect();
+            ContextValue::Table(table)
+        })
+        .collect();
+    Ok(ContextValue::List(items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_data_files() {
+        assert_eq!(data_keys("_data/nav.toml"), Some(vec!["nav"]));
+        assert_eq!(
+            data_keys("_data/blog/authors.json"),
+            Some(vec!["blog", "authors"])
+        );
+        assert_eq!(data_keys("_data/team.csv"), Some(vec!["team"]));
+
+        // Only supported files directly beneath the source directory are data.
+        assert_eq!(data_keys("_data/notes.txt"), None);
+        assert_eq!(data_keys("blog/_data/nav.toml"), None);
+        assert_eq!(data_keys("_header.html"), None);
+    }
+
+    #[test]
+    fn parses_csv() {
+        let csv = "name,role\r\nAda,\"Engineer, Lead\"\r\n\r\n\"Grace \"\"G\"\"\",Admiral\nAlan\n";
+        let ContextValue::List(rows) = parse_csv(csv).unwrap() else {
+            panic!("expected list");
+        };
+        assert_eq!(rows.len(), 3);
+
+        let field = |row: &ContextValue, key: &str| match row {
+            ContextValue::Table(table) => match table.get(&key.into()) {
+                Some(ContextValue::Text(value)) => Some(value.to_string()),
+                _ => None,
+            },
+            _ => panic!("expected table"),
+        };
+        assert_eq!(field(&rows[0], "role").as_deref(), Some("Engineer, Lead"));
+        assert_eq!(field(&rows[1], "name").as_deref(), Some("Grace \"G\""));
+        assert_eq!(field(&rows[2], "name").as_deref(), Some("Alan"));
+        assert_eq!(field(&rows[2], "role"), None);
+
+        assert!(parse_csv("name\n\"Ada").is_err());
+    }
+
+    #[test]
+    fn parses_exact_json_numbers() {
+        let ContextValue::List(numbers) = parse(
+            "numbers.json",
+            b"[9007199254740993, 18446744073709551615, 1.5]",
+        )
+        .unwrap() else {
+            panic!("expected list");
+        };
+        let numbers: Vec<_> = numbers.iter().map(|n| n.to_text().unwrap()).collect();
+        assert_eq!(numbers, ["9007199254740993", "18446744073709551615", "1.5"]);
+    }
+
+    #[test]
+    fn inserts_nested_data() {
+        let mut context = Context::default();
+        insert(&mut context, &["kit", "nav"], ContextValue::List(vec![]));
+        insert(&mut context, &["team"], ContextValue::List(vec![]));
+
+        let Some(ContextValue::Table(data)) = context.get(&DATA_CONTEXT_KEY.into()) else {
+            panic!("missing data");
+        };
+        assert!(matches!(
+            data.get(&"team".into()),
+            Some(ContextValue::List(_))
+        ));
+        assert!(matches!(
+            data.get(&"kit".into()),
+            Some(ContextValue::Table(kit)) if kit.contains_key(&"nav".into())
+        ));
+    }
+}
@@ -20,13 +20,17 @@ use crate::proc::{
     favicon::FaviconProcessor,
     image::ImageResizeProcessor,
     js_bundle::JsBundleProcessor,
-    markdown::MarkdownProcessor,
+    markdown::{Locale, MarkdownProcessor, Punctuation, highlight_stylesheet},
     minify_html::MinifyHtmlProcessor,
     minify_js::MinifyJsProcessor,
     scss::ScssProcessor,
-    template::{PART_CONTEXT_PREFIX, PART_DEFAULTS_PREFIX, TemplateProcessor},
+    template::{
+        PART_CONTEXT_PREFIX, PART_DEFAULTS_PREFIX, PART_LINES_PREFIX, TemplateCache,
+        TemplateProcessor, query_assets, slugify,
+    },
 };
 use crate::tool::DEFAULT_CONFIG_FILE;
+use crate::tool::data;
 use crate::tool::kits::{self, ResolvedKit};
 use crate::tool::{ToolConfig, ToolsMap, opengraph};
 
@@ -36,6 +40,23 @@ const PART_PATH_PREFIX: &str = "_";
 /// Prefix used to store completed asset metadata in the processing context.
 pub const ASSET_PATH_CONTEXT_KEY_PREFIX: &str = "_assets:";
 
+/// Frontmatter key configuring the pagination of an asset.
+const PAGINATE_CONTEXT_KEY: &str = "paginate";
+
+/// Key used to store the current page's state in the processing
+/// context of a paginated asset.
+const PAGINATION_CONTEXT_KEY: &str = "pagination";
+
+/// Number of items per page when a paginated asset doesn't specify one.
+const DEFAULT_ITEMS_PER_PAGE: usize = 10;
+
+/// Key used to store the current page's state in the
+/// processing context of a generated taxonomy page.
+const TAXONOMY_CONTEXT_KEY: &str = "taxonomy";
+
+/// Placeholder replaced with each value's slug in a taxonomy's `path`.
+const TAXONOMY_SLUG_PLACEHOLDER: &str = "{slug}";
+
 /// Appends a value to the `_assets:` list at `key`, creating it if absent.
 fn context_push_asset(context: &mut Context, key: codas::types::Text, value: ContextValue) {
     match context.get_mut(&key) {
@@ -58,9 +79,30 @@ fn register_part(context: &mut Context, key: &str, content: &[u8]) {
     if let Some(defaults) = defaults {
         let ctx_key = format!("{}{}", PART_DEFAULTS_PREFIX, key);
         context.insert(ctx_key.into(), ContextValue::Table(defaults));
+        let lines_key = format!("{}{}", PART_LINES_PREFIX, key);
+        let lines = frontmatter_lines(&content_str, body);
+        context.insert(
+            lines_key.into(),
+            ContextValue::Text(lines.to_string().into()),
+        );
     }
 }
 
+/// Loads a data file into the processing context beneath `keys`
+/// (see [data::insert]), logging an error if it's malformed.
+fn register_data(context: &mut Context, path: &str, keys: &[&str], content: &[u8]) {
+    match data::parse(path, content) {
+        Ok(value) => data::insert(context, keys, value),
+        Err(e) => tracing::error!("Failed to load data from {}: {}", path, e),
+    }
+}
+
+/// Returns the number of lines of frontmatter that
+/// were stripped from `text`, leaving `body`.
+fn frontmatter_lines(text: &str, body: &str) -> usize {
+    text[..text.len() - body.len()].matches('\n').count()
+}
+
 /// Returns true if the path represents a part.
 pub fn is_part(path: &str) -> bool {
     path.split(['/', '\\'])
@@ -111,6 +153,7 @@ pub async fn run(procs_file: Option<&Path>, profile: Option<&str>) -> std::io::R
             target,
             procs: &config.procs,
             tools: &config.tools,
+            taxonomies: &config.taxonomies,
             clean_urls,
             resolved_kits: &resolved_kits,
             project_root: &loaded.config_dir,
@@ -153,6 +196,7 @@ pub struct BuildConfig<'a> {
     pub target: &'a Path,
     pub procs: &'a BTreeMap<String, ProcessorConfig>,
     pub tools: &'a ToolsMap,
+    pub taxonomies: &'a BTreeMap<String, TaxonomyConfig>,
     pub clean_urls: bool,
     pub resolved_kits: &'a [ResolvedKit],
     pub project_root: &'a Path,
@@ -163,6 +207,8 @@ pub struct BuildConfig<'a> {
 /// Parts (files with `_`-prefixed path components) are cached in `context`
 /// and the remaining assets are processed in a convergence loop: all assets
 /// are processed in parallel, then reprocessed until outputs stabilize.
+///
+/// Fails if any asset failed to process in the final pass.
 pub async fn build_assets(config: &BuildConfig<'_>, context: &mut Context) -> std::io::Result<()> {
     let source = config.source;
     let target = config.target;
@@ -185,11 +231,14 @@ pub async fn build_assets(config: &BuildConfig<'_>, context: &mut Context) -> st
         .map(|kit| (kit.name.clone(), kit.local_path.clone()))
         .collect();
 
-    // Separate parts from regular assets and cache them in context.
+    // Separate data files and parts from regular assets and cache them in context.
     let mut regular_assets = Vec::new();
     let mut part_count = 0;
     for (relative_path, content) in assets {
-        if is_part(&relative_path) {
+        if let Some(keys) = data::data_keys(&relative_path) {
+            register_data(context, &relative_path, &keys, &content);
+            tracing::debug!("Found data: {}", relative_path);
+        } else if is_part(&relative_path) {
             register_part(context, &relative_path, &content);
             part_count += 1;
             tracing::debug!("Found part: {}", relative_path);
@@ -223,7 +272,12 @@ pub async fn build_assets(config: &BuildConfig<'_>, context: &mut Context) -> st
                 format!("{}/{}", dest_trimmed, &relative_path)
             };
 
-            if is_part(&relative_path) {
+            if let Some(keys) = data::data_keys(&relative_path) {
+                // Store kit data beneath the kit's name.
+                let keys: Vec<&str> = std::iter::once(kit.name.as_str()).chain(keys).collect();
+                register_data(context, &relative_path, &keys, &content);
+                tracing::debug!("Found kit data: {}/{}", kit.name, relative_path);
+            } else if is_part(&relative_path) {
                 // Store kit parts as {kit_name}/{path} for template resolution.
                 let kit_relative = format!("{}/{}", kit.name, relative_path);
                 register_part(context, &kit_relative, &content);
@@ -311,12 +365,27 @@ pub async fn build_assets(config: &BuildConfig<'_>, context: &mut Context) -> st
         }
     }
 
+    // Generate the theme stylesheet for highlighted code blocks.
+    if let Some(path) = procs
+        .get("markdown")
+        .and_then(|config| config.highlight_theme.as_ref())
+    {
+        let path = path.trim_start_matches('/').to_string();
+        if regular_assets.iter().any(|(existing, _)| *existing == path) {
+            tracing::warn!("Highlight theme collides with asset `{}`", path);
+        } else {
+            let stylesheet = highlight_stylesheet(path.ends_with(".scss"));
+            regular_assets.push((path, stylesheet.into_bytes()));
+        }
+    }
+
     // Process all assets in a convergence loop: process everything,
     // then reprocess until asset outputs stabilize.
     let procs = Arc::new(procs.clone());
     let target = Arc::new(target.to_path_buf());
     let mut asset_outputs: BTreeMap<String, String> = BTreeMap::new();
-    let mut error_count;
+    let templates = Arc::new(TemplateCache::default());
+    let mut error_count = 0;
     let max_passes = 10;
 
     for pass in 0..max_passes {
@@ -327,6 +396,7 @@ pub async fn build_assets(config: &BuildConfig<'_>, context: &mut Context) -> st
             source_root: source.to_path_buf(),
             kit_imports: kit_imports.clone(),
             asset_outputs: asset_outputs.clone(),
+            templates: templates.clone(),
         });
 
         // Share the base context across all tasks via Arc.
@@ -358,10 +428,20 @@ pub async fn build_assets(config: &BuildConfig<'_>, context: &mut Context) -> st
 
         for handle in handles {
             match handle.await {
-                Ok((path, Ok(result))) => {
+                Ok((path, Ok(pages))) => {
                     success_count += 1;
-                    asset_outputs.insert(path.clone(), result.output_path);
-                    pass_results.push((path, result.metadata));
+
+                    // Only the first page of a paginated asset is
+                    // listed in the _assets: context, but every page
+                    // is registered as an output.
+                    let mut pages = pages.into_iter().enumerate();
+                    if let Some((_, first)) = pages.next() {
+                        asset_outputs.insert(path.clone(), first.output_path);
+                        pass_results.push((path.clone(), first.metadata));
+                    }
+                    for (i, page) in pages {
+                        asset_outputs.insert(page_output_key(&path, i + 1), page.output_path);
+                    }
                 }
                 Ok((path, Err(e))) => {
                     tracing::error!("Error processing {}: {}", path, e);
@@ -384,11 +464,28 @@ pub async fn build_assets(config: &BuildConfig<'_>, context: &mut Context) -> st
         }
 
         // Insert processed asset metadata into context.
-        for (path, metadata) in pass_results {
+        for (path, metadata) in &pass_results {
             let dir = path.rsplit_once('/').map(|(d, _)| d).unwrap_or("");
             let key: codas::types::Text =
                 format!("{}{}", ASSET_PATH_CONTEXT_KEY_PREFIX, dir).into();
-            context_push_asset(context, key, ContextValue::Table(metadata));
+            context_push_asset(context, key, ContextValue::Table(metadata.clone()));
+        }
+
+        // Generate taxonomy pages from the collected metadata.
+        let base = Arc::new(context.clone());
+        let taxonomy_results =
+            process_taxonomies(config, &pass_results, &asset_outputs, &procs, &env, base).await;
+        for (path, result) in taxonomy_results {
+            match result {
+                Ok(page) => {
+                    success_count += 1;
+                    asset_outputs.insert(path, page.output_path);
+                }
+                Err(e) => {
+                    tracing::error!("Error processing {}: {}", path, e);
+                    error_count += 1;
+                }
+            }
         }
 
         // Check if asset outputs changed during this pass.
@@ -407,6 +504,13 @@ pub async fn build_assets(config: &BuildConfig<'_>, context: &mut Context) -> st
         }
     }
 
+    if error_count > 0 {
+        return Err(std::io::Error::other(format!(
+            "failed to process {} assets",
+            error_count
+        )));
+    }
+
     Ok(())
 }
 
@@ -439,6 +543,22 @@ pub struct ProcessedAsset {
     pub metadata: AssetMetadata,
 }
 
+/// A single page of a paginated asset or a taxonomy.
+struct Page {
+    /// The 1-based number of this page.
+    number: usize,
+
+    /// The page's state (like [PAGINATION_CONTEXT_KEY]), inserted into
+    /// its processing context and excluded from its metadata.
+    context: Context,
+}
+
+/// Processes a single asset, returning one [ProcessedAsset]
+/// per page of output.
+///
+/// Most assets produce exactly one page. Assets with a `paginate`
+/// table in their frontmatter produce one page per `per_page` items
+/// of the assets they paginate over (see [paginate]).
 pub async fn process_asset(
     path: &str,
     content: Vec<u8>,
@@ -447,24 +567,313 @@ pub async fn process_asset(
     base: Arc<Context>,
     target: &Path,
     clean_urls: bool,
+) -> std::io::Result<Vec<ProcessedAsset>> {
+    let Some(pages) = paginate(path, &content, env, &base, clean_urls) else {
+        let asset = Asset::new(path.into(), content);
+        let processed = process_page(asset, procs, env, base, target, clean_urls, None).await?;
+        return Ok(vec![processed]);
+    };
+
+    let mut processed = Vec::with_capacity(pages.len());
+    for page in &pages {
+        processed.push(
+            process_page(
+                Asset::new(path.into(), content.clone()),
+                procs,
+                env,
+                base.clone(),
+                target,
+                clean_urls,
+                Some(page),
+            )
+            .await?,
+        );
+    }
+    Ok(processed)
+}
+
+/// Splits the assets an asset paginates over into [Page]s, returning
+/// `None` if the asset's frontmatter doesn't contain a `paginate` table:
+///
+/// ```toml
+/// paginate = { assets = "logs", per_page = 10, sort = "date desc" }
+/// ```
+///
+/// The first page is written to the asset's usual output path, and
+/// each following page `N` to `page/N/index.html` beside it.
+fn paginate(
+    path: &str,
+    content: &[u8],
+    env: &Environment,
+    base: &Arc<Context>,
+    clean_urls: bool,
+) -> Option<Vec<Page>> {
+    let text: codas::types::Text = std::str::from_utf8(content).ok()?.into();
+    let (_, Some(frontmatter)) = extract_frontmatter(&text) else {
+        return None;
+    };
+    let ContextValue::Table(config) = frontmatter.get(&PAGINATE_CONTEXT_KEY.into())? else {
+        tracing::warn!("Ignoring non-table `paginate` in {}", path);
+        return None;
+    };
+
+    let text_value = |key: &str| config.get(&key.into()).and_then(ContextValue::to_text);
+    let Some(dir_path) = text_value("assets") else {
+        tracing::warn!("Missing `paginate.assets` in {}", path);
+        return None;
+    };
+    let per_page = text_value("per_page")
+        .and_then(|n| n.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_ITEMS_PER_PAGE);
+    let sort = text_value("sort");
+
+    let context = LayeredContext::new(base.clone());
+    let items = match query_assets(env, &context, &dir_path, sort.as_deref()) {
+        Ok(items) => items,
+        Err(e) => {
+            tracing::warn!("Failed to paginate {}: {}", path, e);
+            return None;
+        }
+    };
+
+    // Always produce at least one (possibly empty) page.
+    let mut chunks: Vec<Vec<ContextValue>> = items
+        .chunks(per_page)
+        .map(<[ContextValue]>::to_vec)
+        .collect();
+    if chunks.is_empty() {
+        chunks.push(vec![]);
+    }
+
+    let total = chunks.len();
+    let pages = chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, items)| {
+            let number = i + 1;
+            let mut pagination = Context::default();
+            pagination.insert("items".into(), ContextValue::List(items));
+            pagination.insert("current".into(), ContextValue::Integer(number as i64));
+            pagination.insert("total".into(), ContextValue::Integer(total as i64));
+            if number > 1 {
+                let prev = page_url(path, number - 1, clean_urls);
+                pagination.insert("prev".into(), ContextValue::Text(prev.into()));
+            }
+            if number < total {
+                let next = page_url(path, number + 1, clean_urls);
+                pagination.insert("next".into(), ContextValue::Text(next.into()));
+            }
+            let mut context = Context::default();
+            context.insert(
+                PAGINATION_CONTEXT_KEY.into(),
+                ContextValue::Table(pagination),
+            );
+            Page { number, context }
+        })
+        .collect();
+
+    Some(pages)
+}
+
+/// Generates and processes the pages of each taxonomy in `config`
+/// from the metadata of all processed `assets`, returning the
+/// generated path of each page with its processing result.
+///
+/// Generated pages are processed like an empty asset at their path,
+/// wrapped in the taxonomy's pattern. Pages which would be written to
+/// the same path as an asset (per its entry in `asset_outputs`) aren't
+/// processed, and fail with an error instead.
+async fn process_taxonomies(
+    config: &BuildConfig<'_>,
+    assets: &[(String, AssetMetadata)],
+    asset_outputs: &BTreeMap<String, String>,
+    procs: &Arc<BTreeMap<String, ProcessorConfig>>,
+    env: &Arc<Environment>,
+    base: Arc<Context>,
+) -> Vec<(String, std::io::Result<ProcessedAsset>)> {
+    if !config.taxonomies.is_empty() && !procs.contains_key("pattern") {
+        tracing::warn!("Taxonomy pages require the `pattern` processor");
+    }
+
+    let mut handles = vec![];
+    let mut results = vec![];
+    for (name, taxonomy) in config.taxonomies {
+        for (path, page) in taxonomy_pages(name, taxonomy, assets, config.clean_urls) {
+            let output_path = if config.clean_urls {
+                rewrite_clean_url_path(&path)
+            } else {
+                path.clone()
+            };
+            if let Some((asset_path, _)) = asset_outputs
+                .iter()
+                .find(|(_, asset_output)| **asset_output == output_path)
+            {
+                let error = std::io::Error::other(format!(
+                    "taxonomy `{}` page collides with asset `{}` at `{}`",
+                    name, asset_path, output_path
+                ));
+                results.push((path, Err(error)));
+                continue;
+            }
+
+            let procs = Arc::clone(procs);
+            let base = Arc::clone(&base);
+            let env = Arc::clone(env);
+            let target = config.target.to_path_buf();
+            let clean_urls = config.clean_urls;
+            handles.push(tokio::spawn(async move {
+                let mut asset = Asset::new(path.as_str().into(), vec![]);
+                asset.replace_with_text("".into(), MediaType::Html);
+                let result =
+                    process_page(asset, &procs, &env, base, &target, clean_urls, Some(&page)).await;
+                (path, result)
+            }));
+        }
+    }
+
+    for handle in handles {
+        match handle.await {
+            Ok(result) => results.push(result),
+            Err(e) => tracing::error!("Task panicked: {}", e),
+        }
+    }
+    results
+}
+
+/// Returns the generated path and [Page] of each distinct value of the
+/// frontmatter field `name` in the metadata of `assets`, followed by the
+/// taxonomy's index page:
+///
+/// ```toml
+/// [default.taxonomies]
+/// tags = { pattern = "_tag.html", path = "tags/{slug}", index = "_tags.html" }
+/// ```
+///
+/// Each value's page is generated at `path` (as `tags/rust.html`), and the
+/// index page in the directory containing the values' pages (as
+/// `tags/index.html`). Values are grouped by their slug, and listed in
+/// slug order; each value's assets are listed in the order of their paths.
+fn taxonomy_pages(
+    name: &str,
+    config: &TaxonomyConfig,
+    assets: &[(String, AssetMetadata)],
+    clean_urls: bool,
+) -> Vec<(String, Page)> {
+    let Some((dir, _)) = config.path.split_once(TAXONOMY_SLUG_PLACEHOLDER) else {
+        tracing::warn!(
+            "Missing `{}` in path of taxonomy `{}`",
+            TAXONOMY_SLUG_PLACEHOLDER,
+            name
+        );
+        return vec![];
+    };
+
+    let mut assets: Vec<_> = assets.iter().collect();
+    assets.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    // Collect each value's assets by slug; lists of values
+    // (like `tags`) add their asset to each value's page.
+    let mut terms: BTreeMap<String, (codas::types::Text, Vec<ContextValue>)> = BTreeMap::new();
+    for (_, metadata) in assets {
+        let values = match metadata.get(&name.into()) {
+            Some(ContextValue::List(values)) => values.iter().collect(),
+            Some(value) => vec![value],
+            None => vec![],
+        };
+
+        let mut slugs = vec![];
+        for value in values {
+            let Some(term) = value.to_text() else {
+                continue;
+            };
+            let slug = slugify(&term);
+            if slug.is_empty() || slugs.contains(&slug) {
+                continue;
+            }
+            slugs.push(slug.clone());
+
+            let (_, items) = terms.entry(slug).or_insert_with(|| (term.clone(), vec![]));
+            items.push(ContextValue::Table(metadata.clone()));
+        }
+    }
+
+    let page = |context: Context, pattern: &str| {
+        let mut page_context = Context::default();
+        page_context.insert(TAXONOMY_CONTEXT_KEY.into(), ContextValue::Table(context));
+        page_context.insert("pattern".into(), ContextValue::Text(pattern.into()));
+        Page {
+            number: 1,
+            context: page_context,
+        }
+    };
+
+    let mut pages = vec![];
+    let mut index = vec![];
+    for (slug, (term, items)) in terms {
+        let path = format!(
+            "{}.html",
+            config.path.replace(TAXONOMY_SLUG_PLACEHOLDER, &slug)
+        );
+
+        let mut entry = Context::default();
+        entry.insert("term".into(), ContextValue::Text(term));
+        entry.insert(
+            "url".into(),
+            ContextValue::Text(page_url(&path, 1, clean_urls).into()),
+        );
+        entry.insert("count".into(), ContextValue::Integer(items.len() as i64));
+        entry.insert("slug".into(), ContextValue::Text(slug.into()));
+        index.push(ContextValue::Table(entry.clone()));
+
+        entry.insert("name".into(), ContextValue::Text(name.into()));
+        entry.insert("items".into(), ContextValue::List(items));
+        pages.push((path, page(entry, &config.pattern)));
+    }
+
+    let mut context = Context::default();
+    context.insert("name".into(), ContextValue::Text(name.into()));
+    context.insert("terms".into(), ContextValue::List(index));
+    let pattern = config.index.as_ref().unwrap_or(&config.pattern);
+    let dir = dir.trim_end_matches('/');
+    let path = if dir.is_empty() {
+        "index.html".to_string()
+    } else {
+        format!("{}/index.html", dir)
+    };
+    pages.push((path, page(context, pattern)));
+
+    pages
+}
+
+/// Processes a single page of an asset through all matching processors.
+async fn process_page(
+    mut asset: Asset,
+    procs: &BTreeMap<String, ProcessorConfig>,
+    env: &Environment,
+    base: Arc<Context>,
+    target: &Path,
+    clean_urls: bool,
+    page: Option<&Page>,
 ) -> std::io::Result<ProcessedAsset> {
-    let mut asset = Asset::new(path.into(), content);
+    let path = asset.path().to_string();
+    let path = path.as_str();
     let mut context = LayeredContext::new(base);
     context.push_layer(); // asset-level overlay
 
+    if let Some(page) = page {
+        context.extend_top(page.context.clone());
+    }
+
     // If canonicalization is enabled, add the asset's canonical
     // path to the processing context.
     if let Some(config) = procs.get("canonicalize")
         && let Some(root) = &config.root
     {
-        // @caer: fixme: This logic is brittle. We should have some way
-        //        to predict the target path based on the final applicable
-        //        processor.
-        let target_path = if path.ends_with(".md") {
-            path.trim_end_matches(".md").to_string() + ".html"
-        } else {
-            path.to_string()
-        };
+        let mut target_path = predict_target_path(path);
+        if let Some(page) = page {
+            target_path = page_output_path(&target_path, page.number);
+        }
 
         // With clean URLs, canonical paths omit the .html extension.
         let canonical_target = if clean_urls && target_path.ends_with(".html") {
@@ -485,10 +894,15 @@ pub async fn process_asset(
 
     // Perform phase one of processing (transformation and pattern wrapping).
     let mut first_pass = true;
+    // The pattern (if any) currently being processed, and the lines of
+    // frontmatter stripped from the template currently being processed.
+    let mut current_pattern: Option<codas::types::Text> = None;
+    let mut line_offset = 0;
     loop {
         if let Ok(text) = asset.as_text() {
             let (body, frontmatter) = extract_frontmatter(text);
             if let Some(parsed) = frontmatter {
+                line_offset = frontmatter_lines(text, body);
                 if first_pass {
                     context.extend_top(parsed);
                 } else {
@@ -515,11 +929,24 @@ pub async fn process_asset(
                 if let Some(config) = procs.get(*proc_name) {
                     match run_processor(proc_name, config, env, &context, &mut asset) {
                         Ok(true) => {
+                            // Metadata from processors doesn't override frontmatter.
+                            context.fill_top(asset.take_metadata());
                             ran_processors.push(proc_name);
                         }
                         Ok(false) => {}
-                        Err(e) => {
-                            tracing::warn!("Processor `{}` failed on {}: {:?}", proc_name, path, e);
+                        Err(mut e) => {
+                            // Pattern content is processed under the asset's
+                            // path; attribute template errors to the pattern.
+                            if let ProcessingError::Template { diagnostic } = &mut e {
+                                let template_path = current_pattern.as_deref().unwrap_or(path);
+                                diagnostic.relocate(path, template_path, line_offset);
+                            }
+
+                            // In strict mode, template errors fail the asset.
+                            if *proc_name == "template" && config.strict.unwrap_or(false) {
+                                return Err(std::io::Error::other(e.to_string()));
+                            }
+                            tracing::warn!("Processor `{}` failed on {}: {}", proc_name, path, e);
                         }
                     }
                 }
@@ -577,6 +1004,13 @@ pub async fn process_asset(
             ran_processors.push("pattern");
             asset = Asset::new(path.into(), pattern_content.as_bytes().to_vec());
             asset.set_media_type(pattern_media_type);
+            let lines_key: codas::types::Text =
+                format!("{}{}", PART_LINES_PREFIX, pattern_path).into();
+            line_offset = match context.get(&lines_key) {
+                Some(ContextValue::Text(lines)) => lines.parse().unwrap_or(0),
+                _ => 0,
+            };
+            current_pattern = Some(pattern_path);
 
             // Continue loop to process the pattern recursively.
             continue;
@@ -594,7 +1028,7 @@ pub async fn process_asset(
                 }
                 Ok(false) => {}
                 Err(e) => {
-                    tracing::warn!("Processor `{}` failed on {}: {:?}", proc_name, path, e);
+                    tracing::warn!("Processor `{}` failed on {}: {}", proc_name, path, e);
                 }
             }
         }
@@ -618,6 +1052,11 @@ pub async fn process_asset(
         processed_path = rewrite_clean_url_path(&processed_path);
     }
 
+    // Following pages of paginated assets are written beside the first.
+    if let Some(page) = page {
+        processed_path = page_output_path(&processed_path, page.number);
+    }
+
     let target_path = target.join(&processed_path);
 
     // Write the processed asset to target.
@@ -648,8 +1087,12 @@ pub async fn process_asset(
         );
     }
 
-    // Return only the page-level overlay as asset metadata.
-    let page_overlay = context.pop_layer().unwrap_or_default();
+    // Return only the page-level overlay as asset metadata,
+    // excluding any (potentially large) page state.
+    let mut page_overlay = context.pop_layer().unwrap_or_default();
+    for key in page.iter().flat_map(|page| page.context.keys()) {
+        page_overlay.remove(key);
+    }
     Ok(ProcessedAsset {
         output_path: processed_path,
         metadata: page_overlay,
@@ -668,8 +1111,25 @@ pub fn run_processor(
     asset: &mut Asset,
 ) -> Result<bool, ProcessingError> {
     match name {
-        "markdown" => MarkdownProcessor {}.process(env, context, asset),
-        "template" => TemplateProcessor.process(env, context, asset),
+        "markdown" => {
+            let highlight = config.highlight.unwrap_or(false);
+            let line_numbers = config.line_numbers.unwrap_or(false);
+            let toc_depth = config.toc_min_depth.unwrap_or(1)..=config.toc_max_depth.unwrap_or(6);
+            let punctuation = match config.smart_punctuation {
+                None => Punctuation::Dashes,
+                Some(true) => {
+                    Punctuation::Smart(Locale::from_code(config.locale.as_deref().unwrap_or("en")))
+                }
+                Some(false) => Punctuation::Plain,
+            };
+            MarkdownProcessor::new(highlight, line_numbers, toc_depth, punctuation)
+                .process(env, context, asset)
+        }
+        "template" => {
+            let strict = config.strict.unwrap_or(false);
+            let trim_blocks = config.trim_blocks.unwrap_or(false);
+            TemplateProcessor::new(strict, trim_blocks).process(env, context, asset)
+        }
         "favicon" => FaviconProcessor.process(env, context, asset),
         "canonicalize" => {
             let root = config.root.as_deref().unwrap_or("http://localhost/");
@@ -700,6 +1160,66 @@ pub fn run_processor(
     }
 }
 
+/// Predicts the output path of an asset from its source path.
+///
+/// @caer: fixme: This logic is brittle. We should have some way
+///        to predict the target path based on the final applicable
+///        processor.
+fn predict_target_path(path: &str) -> String {
+    if path.ends_with(".md") {
+        path.trim_end_matches(".md").to_string() + ".html"
+    } else {
+        path.to_string()
+    }
+}
+
+/// Returns the key under which page `number` of the asset
+/// at `path` is registered in [Environment::asset_outputs].
+fn page_output_key(path: &str, number: usize) -> String {
+    if number > 1 {
+        format!("{}#page/{}", path, number)
+    } else {
+        path.to_string()
+    }
+}
+
+/// Rewrites an HTML output path for page `number` of a paginated asset.
+/// Page 1 is unchanged; later pages of `blog.html` or `blog/index.html`
+/// become `blog/page/N/index.html`.
+fn page_output_path(path: &str, number: usize) -> String {
+    if number <= 1 {
+        return path.to_string();
+    }
+
+    let filename = path.rsplit('/').next().unwrap_or(path);
+    let stem = if filename == "index.html" {
+        path[..path.len() - filename.len()].trim_end_matches('/')
+    } else {
+        path.strip_suffix(".html").unwrap_or(path)
+    };
+
+    if stem.is_empty() {
+        format!("page/{}/index.html", number)
+    } else {
+        format!("{}/page/{}/index.html", stem, number)
+    }
+}
+
+/// Returns the root-relative URL of page `number` of the asset at `path`.
+fn page_url(path: &str, number: usize, clean_urls: bool) -> String {
+    let mut output_path = predict_target_path(path);
+    if clean_urls && output_path.ends_with(".html") {
+        output_path = rewrite_clean_url_path(&output_path);
+    }
+    let output_path = page_output_path(&output_path, number);
+
+    if clean_urls && output_path.ends_with(".html") {
+        format!("/{}", rewrite_clean_url_canonical(&output_path))
+    } else {
+        format!("/{}", output_path)
+    }
+}
+
 /// Rewrites an HTML output path for clean URLs.
 /// `slug.html` becomes `slug/index.html`; `index.html` is unchanged.
 fn rewrite_clean_url_path(path: &str) -> String {
@@ -724,6 +1244,18 @@ fn rewrite_clean_url_canonical(path: &str) -> String {
     }
 }
 
+/// Configuration for a taxonomy of pages generated
+/// from the values of a frontmatter field (see [taxonomy_pages]).
+#[derive(Debug, Deserialize, Clone)]
+pub struct TaxonomyConfig {
+    /// The pattern wrapping each value's page.
+    pattern: String,
+    /// The path of each value's page, containing `{slug}`.
+    path: String,
+    /// The pattern wrapping the index page, if not [Self::pattern].
+    index: Option<String>,
+}
+
 /// Configuration for a single processor.
 #[derive(Debug, Default, Deserialize, Clone)]
 pub struct ProcessorConfig {
@@ -734,6 +1266,17 @@ pub struct ProcessorConfig {
     // image options
     max_width: Option<u32>,
     max_height: Option<u32>,
+    // template options
+    strict: Option<bool>,
+    trim_blocks: Option<bool>,
+    // markdown options
+    highlight: Option<bool>,
+    line_numbers: Option<bool>,
+    highlight_theme: Option<String>,
+    toc_min_depth: Option<u8>,
+    toc_max_depth: Option<u8>,
+    smart_punctuation: Option<bool>,
+    locale: Option<String>,
 }
 
 #[cfg(test)]
@@ -780,4 +1323,181 @@ mod tests {
         // Nested index.html becomes directory path.
         assert_eq!(rewrite_clean_url_canonical("blog/index.html"), "blog/");
     }
+
+    #[test]
+    fn rewrites_page_output_paths() {
+        // The first page is unchanged.
+        assert_eq!(page_output_path("blog.html", 1), "blog.html");
+        assert_eq!(page_output_path("blog/index.html", 1), "blog/index.html");
+
+        // Later pages are nested beside the first.
+        assert_eq!(page_output_path("index.html", 2), "page/2/index.html");
+        assert_eq!(page_output_path("blog.html", 2), "blog/page/2/index.html");
+        assert_eq!(
+            page_output_path("blog/index.html", 3),
+            "blog/page/3/index.html"
+        );
+    }
+
+    #[test]
+    fn counts_frontmatter_lines() {
+        let text: codas::types::Text = "title = \"Hi\"\ndate = 2025-01-01\n***\n<p>Hi</p>".into();
+        let (body, _) = extract_frontmatter(&text);
+        assert_eq!(frontmatter_lines(&text, body), 2);
+
+        let text: codas::types::Text = "<p>Hi</p>".into();
+        let (body, _) = extract_frontmatter(&text);
+        assert_eq!(frontmatter_lines(&text, body), 0);
+    }
+
+    #[test]
+    fn computes_page_urls() {
+        assert_eq!(page_url("blog.md", 1, false), "/blog.html");
+        assert_eq!(page_url("blog.md", 2, false), "/blog/page/2/index.html");
+        assert_eq!(page_url("index.html", 2, false), "/page/2/index.html");
+
+        // Clean URLs omit index.html.
+        assert_eq!(page_url("blog.md", 1, true), "/blog/");
+        assert_eq!(page_url("blog.md", 2, true), "/blog/page/2/");
+        assert_eq!(page_url("index.html", 1, true), "/");
+        assert_eq!(page_url("index.html", 2, true), "/page/2/");
+    }
+
+    #[test]
+    fn paginates_assets() {
+        let mut base = Context::default();
+        let logs = (1..=5)
+            .map(|i| {
+                let mut entry = Context::default();
+                entry.insert(
+                    "title".into(),
+                    ContextValue::Text(format!("Log {}", i).into()),
+                );
+                entry.insert(
+                    "date".into(),
+                    ContextValue::Text(format!("2025-01-0{}", i).into()),
+                );
+                ContextValue::Table(entry)
+            })
+            .collect();
+        base.insert("_assets:logs".into(), ContextValue::List(logs));
+        let base = Arc::new(base);
+
+        let content =
+            b"paginate = { assets = \"logs\", per_page = 2, sort = \"date desc\" }\n***\nbody";
+        let pages = paginate("blog.html", content, &Environment::test(), &base, true).unwrap();
+        assert_eq!(pages.len(), 3);
+
+        let pagination = |page: &Page| match page.context.get(&PAGINATION_CONTEXT_KEY.into()) {
+            Some(ContextValue::Table(pagination)) => pagination.clone(),
+            _ => panic!("missing pagination"),
+        };
+        let text = |page: &Page, key: &str| match pagination(page).get(&key.into()) {
+            Some(ContextValue::Text(value)) => Some(value.to_string()),
+            _ => None,
+        };
+        let titles = |page: &Page| match pagination(page).get(&"items".into()) {
+            Some(ContextValue::List(items)) => items
+                .iter()
+                .map(|item| match item {
+                    ContextValue::Table(table) => match table.get(&"title".into()) {
+                        Some(ContextValue::Text(title)) => title.to_string(),
+                        _ => panic!("missing title"),
+                    },
+                    _ => panic!("expected table"),
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("missing items"),
+        };
+
+        assert_eq!(titles(&pages[0]), vec!["Log 5", "Log 4"]);
+        let integer = |page: &Page, key: &str| match pagination(page).get(&key.into()) {
+            Some(ContextValue::Integer(value)) => Some(*value),
+            _ => None,
+        };
+        assert_eq!(integer(&pages[0], "current"), Some(1));
+        assert_eq!(integer(&pages[0], "total"), Some(3));
+        assert_eq!(text(&pages[0], "prev"), None);
+        assert_eq!(text(&pages[0], "next").as_deref(), Some("/blog/page/2/"));
+
+        assert_eq!(titles(&pages[1]), vec!["Log 3", "Log 2"]);
+        assert_eq!(text(&pages[1], "prev").as_deref(), Some("/blog/"));
+        assert_eq!(text(&pages[1], "next").as_deref(), Some("/blog/page/3/"));
+
+        assert_eq!(titles(&pages[2]), vec!["Log 1"]);
+        assert_eq!(text(&pages[2], "next"), None);
+
+        // Assets without a paginate table aren't paginated.
+        assert!(paginate("blog.html", b"body", &Environment::test(), &base, true).is_none());
+    }
+
+    #[test]
+    fn generates_taxonomy_pages() {
+        let asset = |title: &str, tags: &[&str]| {
+            let mut metadata = Context::default();
+            metadata.insert("title".into(), ContextValue::Text(title.into()));
+            let tags = tags.iter().map(|tag| ContextValue::Text((*tag).into()));
+            metadata.insert("tags".into(), ContextValue::List(tags.collect()));
+            metadata
+        };
+        let assets = vec![
+            ("posts/b.md".to_string(), asset("B", &["Rust", "Web Dev"])),
+            ("posts/a.md".to_string(), asset("A", &["rust", "Rust"])),
+            ("posts/c.md".to_string(), asset("C", &[])),
+        ];
+        let config = TaxonomyConfig {
+            pattern: "_tag.html".into(),
+            path: "tags/{slug}".into(),
+            index: Some("_tags.html".into()),
+        };
+
+        let pages = taxonomy_pages("tags", &config, &assets, true);
+        let paths: Vec<_> = pages.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["tags/rust.html", "tags/web-dev.html", "tags/index.html"]
+        );
+
+        let text = |table: &Context, key: &str| match table.get(&key.into()) {
+            Some(ContextValue::Text(value)) => value.to_string(),
+            _ => panic!("missing {}", key),
+        };
+        let taxonomy = |page: &Page| match page.context.get(&TAXONOMY_CONTEXT_KEY.into()) {
+            Some(ContextValue::Table(taxonomy)) => taxonomy.clone(),
+            _ => panic!("missing taxonomy"),
+        };
+        let list = |table: &Context, key: &str| match table.get(&key.into()) {
+            Some(ContextValue::List(items)) => items
+                .iter()
+                .map(|item| match item {
+                    ContextValue::Table(table) => table.clone(),
+                    _ => panic!("expected table"),
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("missing {}", key),
+        };
+
+        // Values with the same slug share a page, listing each asset once.
+        let rust = taxonomy(&pages[0].1);
+        assert_eq!(text(&rust, "term"), "rust");
+        assert_eq!(text(&rust, "url"), "/tags/rust/");
+        assert!(matches!(
+            rust.get(&"count".into()),
+            Some(ContextValue::Integer(2))
+        ));
+        let titles: Vec<_> = list(&rust, "items")
+            .iter()
+            .map(|item| text(item, "title"))
+            .collect();
+        assert_eq!(titles, vec!["A", "B"]);
+        assert_eq!(text(&pages[0].1.context, "pattern"), "_tag.html");
+
+        let index = taxonomy(&pages[2].1);
+        let terms: Vec<_> = list(&index, "terms")
+            .iter()
+            .map(|term| text(term, "term"))
+            .collect();
+        assert_eq!(terms, vec!["rust", "Web Dev"]);
+        assert_eq!(text(&pages[2].1.context, "pattern"), "_tags.html");
+    }
 }
@@ -44,6 +44,7 @@ pub async fn run(port: u16, profile: Option<&str>) -> std::io::Result<()> {
         target: &target,
         procs: &config.procs,
         tools: &config.tools,
+        taxonomies: &config.taxonomies,
         clean_urls,
         resolved_kits: &resolved_kits,
         project_root: &project_root,
@@ -51,7 +52,9 @@ pub async fn run(port: u16, profile: Option<&str>) -> std::io::Result<()> {
 
     // Run initial build.
     tracing::info!("Running initial build...");
-    build(&build_config, &config.context).await?;
+    if let Err(e) = build(&build_config, &config.context).await {
+        tracing::error!("Initial build failed: {}", e);
+    }
 
     // Create channel for rebuild signals.
     let (rebuild_tx, mut rebuild_rx) = mpsc::channel::<()>(1);
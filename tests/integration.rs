@@ -102,7 +102,7 @@ async fn template_markdown_pattern_pipeline() {
 </head>
 <body>
 {~ use "parts/_header.html"}
-<main>{~ get content}</main>
+<main>{~ raw content}</main>
 <footer>copyright {~ get year}</footer>
 </body>
 </html>"#,
@@ -121,7 +121,7 @@ async fn template_markdown_pattern_pipeline() {
 <p class="byline">by {~ get author} on {~ date date "%B %d, %Y"}</p>
 <p class="subtitle">{~ get subtitle or description}</p>
 {~ if not hide_bio}<p class="bio">About the author.</p>{~ end}
-{~ get content}
+{~ raw content}
 </article>"#,
     )
     .await
@@ -297,6 +297,39 @@ The **second** post."#,
     );
 }
 
+/// Exercises escaping in Markdown templates: values escaped by
+/// the template processor stay escaped after Markdown compilation.
+#[tokio::test]
+async fn markdown_template_escaping() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path();
+    let site = root.join("site");
+    let public = root.join("public");
+
+    fs::create_dir_all(&site).await.unwrap();
+    write_config(root, &site, &public, "template = {}\nmarkdown = {}", "").await;
+
+    fs::write(
+        site.join("post.md"),
+        "title = \"<script>alert(1)</script> & co\"\n\n***\n# {~ get title}\n\nBy {~ get title}.",
+    )
+    .await
+    .unwrap();
+
+    run_aer(root).await;
+
+    let post = read(&public, "post.html").await;
+    assert!(!post.contains("<script>"), "post:\n{post}");
+    assert!(
+        post.contains("&lt;script&gt;alert(1)&lt;/script&gt; &amp; co</h1>"),
+        "post:\n{post}"
+    );
+    assert!(
+        post.contains("<p>By &lt;script&gt;alert(1)&lt;/script&gt; &amp; co.</p>"),
+        "post:\n{post}"
+    );
+}
+
 /// Exercises SCSS-to-CSS compilation with variables and nesting.
 #[tokio::test]
 async fn scss_compilation() {
@@ -569,6 +602,450 @@ template = {{}}
     );
 }
 
+/// Exercises pagination: a single template paginating over a directory of
+/// posts is written to one output page per `per_page` posts.
+#[tokio::test]
+async fn pagination() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path();
+    let site = root.join("site");
+    let public = root.join("public");
+
+    fs::create_dir_all(site.join("posts")).await.unwrap();
+
+    let config = format!(
+        r#"
+[default.paths]
+source = "{}"
+target = "{}"
+clean_urls = true
+
+[default.procs]
+template = {{}}
+markdown = {{}}
+"#,
+        site.to_string_lossy(),
+        public.to_string_lossy(),
+    );
+    fs::write(root.join("Aer.toml"), config).await.unwrap();
+
+    for i in 1..=5 {
+        fs::write(
+            site.join(format!("posts/post-{i}.md")),
+            format!("title = \"Post {i}\"\ndate = \"2025-01-0{i}\"\n\n***\nBody {i}."),
+        )
+        .await
+        .unwrap();
+    }
+
+    fs::write(
+        site.join("index.html"),
+        r#"paginate = { assets = "posts", per_page = 2, sort = "date desc" }
+
+***
+{~ for post in pagination.items}<li>{~ get post.title}</li>{~ end}
+<p>Page {~ get pagination.current} of {~ get pagination.total}</p>
+{~ if pagination.prev}<a rel="prev" href="{~ get pagination.prev}">Newer</a>{~ end}
+{~ if pagination.next}<a rel="next" href="{~ get pagination.next}">Older</a>{~ end}"#,
+    )
+    .await
+    .unwrap();
+
+    run_aer(root).await;
+
+    let first = read(&public, "index.html").await;
+    assert!(
+        first.contains("<li>Post 5</li><li>Post 4</li>"),
+        "first page:\n{first}"
+    );
+    assert!(first.contains("Page 1 of 3"), "first page:\n{first}");
+    assert!(!first.contains("rel=\"prev\""), "first page:\n{first}");
+    assert!(
+        first.contains(r#"<a rel="next" href="/page/2/">"#),
+        "first page:\n{first}"
+    );
+
+    let second = read(&public, "page/2/index.html").await;
+    assert!(
+        second.contains("<li>Post 3</li><li>Post 2</li>"),
+        "second page:\n{second}"
+    );
+    assert!(
+        second.contains(r#"<a rel="prev" href="/">"#),
+        "second page:\n{second}"
+    );
+    assert!(
+        second.contains(r#"<a rel="next" href="/page/3/">"#),
+        "second page:\n{second}"
+    );
+
+    let third = read(&public, "page/3/index.html").await;
+    assert!(third.contains("<li>Post 1</li>"), "third page:\n{third}");
+    assert!(third.contains("Page 3 of 3"), "third page:\n{third}");
+    assert!(!third.contains("rel=\"next\""), "third page:\n{third}");
+    assert!(!exists(&public, "page/4/index.html").await);
+}
+
+/// Exercises taxonomy pages: one page per distinct tag, plus
+/// an index page of all tags, each wrapped in a pattern.
+#[tokio::test]
+async fn taxonomies() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path();
+    let site = root.join("site");
+    let public = root.join("public");
+
+    fs::create_dir_all(site.join("posts")).await.unwrap();
+
+    let config = format!(
+        r#"
+[default.paths]
+source = "{}"
+target = "{}"
+clean_urls = true
+
+[default.procs]
+template = {{}}
+markdown = {{}}
+pattern = {{}}
+
+[default.taxonomies]
+tags = {{ pattern = "_tag.html", path = "tags/{{slug}}", index = "_tags.html" }}
+"#,
+        site.to_string_lossy(),
+        public.to_string_lossy(),
+    );
+    fs::write(root.join("Aer.toml"), config).await.unwrap();
+
+    for (i, tags) in [(1, r#""Rust", "Web Dev""#), (2, r#""Rust""#), (3, "")] {
+        fs::write(
+            site.join(format!("posts/post-{i}.md")),
+            format!(
+                "title = \"Post {i}\"\ndate = \"2025-01-0{i}\"\ntags = [{tags}]\n\n***\nBody {i}."
+            ),
+        )
+        .await
+        .unwrap();
+    }
+
+    fs::write(
+        site.join("_tag.html"),
+        r#"<h1>{~ get taxonomy.term}</h1>
+{~ for post in taxonomy.items sort date desc}<li>{~ get post.title}</li>{~ end}"#,
+    )
+    .await
+    .unwrap();
+    fs::write(
+        site.join("_tags.html"),
+        r#"{~ for tag in taxonomy.terms}<a href="{~ get tag.url}">{~ get tag.term} ({~ get tag.count})</a>{~ end}"#,
+    )
+    .await
+    .unwrap();
+
+    run_aer(root).await;
+
+    let rust = read(&public, "tags/rust/index.html").await;
+    assert!(rust.contains("<h1>Rust</h1>"), "rust:\n{rust}");
+    assert!(
+        rust.contains("<li>Post 2</li><li>Post 1</li>"),
+        "rust:\n{rust}"
+    );
+
+    let web_dev = read(&public, "tags/web-dev/index.html").await;
+    assert!(web_dev.contains("<li>Post 1</li>"), "web dev:\n{web_dev}");
+    assert!(!web_dev.contains("Post 2"), "web dev:\n{web_dev}");
+
+    let index = read(&public, "tags/index.html").await;
+    assert!(
+        index.contains(
+            r#"<a href="/tags/rust/">Rust (2)</a><a href="/tags/web-dev/">Web Dev (1)</a>"#
+        ),
+        "index:\n{index}"
+    );
+}
+
+/// Exercises taxonomy pages colliding with assets: pages which would
+/// overwrite an asset's output aren't generated, and fail the build.
+#[tokio::test]
+async fn taxonomy_collisions() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path();
+    let site = root.join("site");
+    let public = root.join("public");
+
+    fs::create_dir_all(site.join("tags")).await.unwrap();
+    write_config(
+        root,
+        &site,
+        &public,
+        "template = {}\nmarkdown = {}\npattern = {}",
+        r#"[default.taxonomies]
+tags = { pattern = "_tag.html", path = "tags/{slug}", index = "_tag.html" }"#,
+    )
+    .await;
+
+    fs::write(
+        site.join("post.md"),
+        "tags = [\"Rust\", \"Web\"]\n\n***\nBody.",
+    )
+    .await
+    .unwrap();
+    fs::write(site.join("tags/rust.md"), "All about Rust.")
+        .await
+        .unwrap();
+    fs::write(site.join("tags/index.md"), "All tags.")
+        .await
+        .unwrap();
+    fs::write(site.join("_tag.html"), "Generated")
+        .await
+        .unwrap();
+
+    // Collisions are reported as errors, failing the build.
+    let result = aer::tool::procs::run(Some(&root.join("Aer.toml")), None).await;
+    assert!(result.is_err());
+
+    let rust = read(&public, "tags/rust.html").await;
+    assert!(rust.contains("All about Rust."), "rust:\n{rust}");
+    let index = read(&public, "tags/index.html").await;
+    assert!(index.contains("All tags."), "index:\n{index}");
+    let web = read(&public, "tags/web.html").await;
+    assert!(web.contains("Generated"), "web:\n{web}");
+}
+
+/// Exercises strict templates: an unresolved variable fails the
+/// build, and the asset containing it isn't written.
+#[tokio::test]
+async fn strict_templates() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path();
+    let site = root.join("site");
+    let public = root.join("public");
+
+    fs::create_dir_all(&site).await.unwrap();
+    write_config(root, &site, &public, "template = { strict = true }", "").await;
+
+    fs::write(site.join("index.html"), "<h1>{~ get titel}</h1>")
+        .await
+        .unwrap();
+    fs::write(site.join("about.html"), "<h1>{~ get site_name}</h1>")
+        .await
+        .unwrap();
+
+    let result = aer::tool::procs::run(Some(&root.join("Aer.toml")), None).await;
+    assert!(result.is_err());
+    assert!(!exists(&public, "index.html").await);
+    assert_eq!(read(&public, "about.html").await, "<h1>Test Site</h1>");
+}
+
+/// Exercises data files: TOML, JSON, and CSV files in `_data/`
+/// are loaded into the context beneath `data`.
+#[tokio::test]
+async fn data_files() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path();
+    let site = root.join("site");
+    let public = root.join("public");
+
+    fs::create_dir_all(site.join("_data/events")).await.unwrap();
+    write_config(root, &site, &public, "template = {}", "").await;
+
+    fs::write(
+        site.join("_data/nav.toml"),
+        "[[links]]\ntitle = \"Home\"\nhref = \"/\"\n\n[[links]]\ntitle = \"Logs\"\nhref = \"/logs/\"\n",
+    )
+    .await
+    .unwrap();
+    fs::write(
+        site.join("_data/team.csv"),
+        "name,role\nAda,\"Engineer, Lead\"\nGrace,Admiral\n",
+    )
+    .await
+    .unwrap();
+    fs::write(
+        site.join("_data/events/2026.json"),
+        r#"[{"title": "Launch", "attendees": 42}]"#,
+    )
+    .await
+    .unwrap();
+
+    fs::write(
+        site.join("index.html"),
+        r#"{~ for link in data.nav.links}<a href="{~ get link.href}">{~ get link.title}</a>{~ end}
+{~ for member in data.team}<li>{~ get member.name}: {~ get member.role}</li>{~ end}
+{~ for event in data.events.2026}<p>{~ get event.title} ({~ get event.attendees})</p>{~ end}"#,
+    )
+    .await
+    .unwrap();
+
+    run_aer(root).await;
+
+    let index = read(&public, "index.html").await;
+    assert!(
+        index.contains(r#"<a href="/">Home</a><a href="/logs/">Logs</a>"#),
+        "index:\n{index}"
+    );
+    assert!(
+        index.contains("<li>Ada: Engineer, Lead</li><li>Grace: Admiral</li>"),
+        "index:\n{index}"
+    );
+    assert!(index.contains("<p>Launch (42)</p>"), "index:\n{index}");
+
+    // Data files aren't written to the target.
+    assert!(!exists(&public, "_data/nav.toml").await);
+}
+
+/// Exercises the table of contents: the outline of a markdown document's
+/// headings is available to its pattern and to asset listings as `toc`.
+#[tokio::test]
+async fn table_of_contents() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path();
+    let site = root.join("site");
+    let public = root.join("public");
+
+    fs::create_dir_all(site.join("docs")).await.unwrap();
+    write_config(
+        root,
+        &site,
+        &public,
+        "markdown = { toc_min_depth = 2 }\ntemplate = {}\npattern = {}",
+        "",
+    )
+    .await;
+
+    fs::write(
+        site.join("_doc.html"),
+        r##"<nav>{~ for h in toc}<a href="#{~ get h.id}">{~ get h.text}</a>{~ for sub in h.children}<a href="#{~ get sub.id}">{~ get sub.text}</a>{~ end}{~ end}</nav>{~ raw content}"##,
+    )
+    .await
+    .unwrap();
+    fs::write(
+        site.join("docs/guide.md"),
+        "title = \"Guide\"\npattern = \"_doc.html\"\n\n***\n# Guide\n## Setup\n### Install\n## Usage\n",
+    )
+    .await
+    .unwrap();
+    fs::write(
+        site.join("index.html"),
+        r#"{~ for doc in assets "docs"}<li>{~ get doc.title} ({~ get doc.toc | length} sections)</li>{~ end}"#,
+    )
+    .await
+    .unwrap();
+
+    run_aer(root).await;
+
+    let guide = read(&public, "docs/guide.html").await;
+    assert!(
+        guide.contains(
+            r##"<nav><a href="#setup">Setup</a><a href="#install">Install</a><a href="#usage">Usage</a></nav>"##
+        ),
+        "guide:\n{guide}"
+    );
+    assert!(
+        guide.contains(r#"<h2 id="setup">Setup</h2>"#)
+            && guide.contains(r#"<h3 id="install">Install</h3>"#),
+        "guide:\n{guide}"
+    );
+
+    let index = read(&public, "index.html").await;
+    assert!(
+        index.contains("<li>Guide (2 sections)</li>"),
+        "index:\n{index}"
+    );
+}
+
+/// Exercises excerpts and reading statistics: markdown assets' excerpts,
+/// word counts, and reading times flow into asset listings, unless
+/// they're set explicitly in frontmatter.
+#[tokio::test]
+async fn excerpts() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path();
+    let site = root.join("site");
+    let public = root.join("public");
+
+    fs::create_dir_all(site.join("logs")).await.unwrap();
+    write_config(root, &site, &public, "markdown = {}\ntemplate = {}", "").await;
+
+    fs::write(
+        site.join("logs/first.md"),
+        "title = \"First\"\n\n***\nIntro *text*.\n\nMore.\n\n<!-- more -->\n\nRest of the log.",
+    )
+    .await
+    .unwrap();
+    fs::write(
+        site.join("logs/second.md"),
+        "title = \"Second\"\nexcerpt = \"Custom.\"\n\n***\nOpening line.\n\nClosing line.",
+    )
+    .await
+    .unwrap();
+    fs::write(
+        site.join("index.html"),
+        r#"{~ for log in assets "logs" sort title}<li>{~ get log.title}: {~ get log.excerpt} ({~ get log.word_count} words, {~ get log.reading_time} min){~ get log.excerpt_html | raw}</li>{~ end}"#,
+    )
+    .await
+    .unwrap();
+
+    run_aer(root).await;
+
+    let index = read(&public, "index.html").await;
+    assert!(
+        index.contains(
+            "<li>First: Intro text. More. (7 words, 1 min)<p>Intro <em>text</em>.</p><p>More.</p></li>"
+        ),
+        "index:\n{index}"
+    );
+    assert!(
+        index.contains("<li>Second: Custom. (4 words, 1 min)<p>Opening line.</p></li>"),
+        "index:\n{index}"
+    );
+}
+
+/// Exercises syntax highlighting: fenced code blocks are highlighted
+/// at build time, and the theme stylesheet is generated as SCSS.
+#[tokio::test]
+async fn syntax_highlighting() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path();
+    let site = root.join("site");
+    let public = root.join("public");
+
+    fs::create_dir_all(&site).await.unwrap();
+    write_config(
+        root,
+        &site,
+        &public,
+        r#"markdown = { highlight = true, line_numbers = true, highlight_theme = "styles/highlight.scss" }
+scss = {}"#,
+        "",
+    )
+    .await;
+
+    fs::write(
+        site.join("post.md"),
+        "```toml {2}\n[site]\ntitle = \"Hi\"\n```\n",
+    )
+    .await
+    .unwrap();
+
+    run_aer(root).await;
+
+    let post = read(&public, "post.html").await;
+    assert!(
+        post.contains(r#"<pre rel="toml" class="highlight line-numbers"><code class="language-toml"><span class="line"><span class="hl-tag">[site]</span></span>"#),
+        "post:\n{post}"
+    );
+    assert!(
+        post.contains(r#"<span class="line highlighted"><span class="hl-property">title</span> = <span class="hl-string">"Hi"</span></span>"#),
+        "post:\n{post}"
+    );
+
+    // The theme is compiled from SCSS.
+    let theme = read(&public, "styles/highlight.css").await;
+    assert!(theme.contains(".highlight .hl-keyword"), "theme:\n{theme}");
+    assert!(theme.contains("#d73a49"), "theme:\n{theme}");
+}
+
 /// Exercises image resizing: oversized images are scaled down while images
 /// within bounds are passed through unchanged.
 #[tokio::test]
@@ -773,7 +1250,7 @@ minify_js = {}"#,
 <link rel="stylesheet" href="/styles/main.css">
 </head>
 <body>
-{~ get content}
+{~ raw content}
 <script src="/js/app.js"></script>
 </body>"#,
     )